@@ -1,17 +1,12 @@
-use serde::{Deserialize, Serialize};
-use std::{borrow::Cow, cell::RefCell, path::PathBuf};
+use std::{cell::RefCell, path::PathBuf};
 
 use clap::Parser;
 use libafl::{
-    HasMetadata,
     corpus::{Corpus, InMemoryCorpus, OnDiskCorpus, Testcase},
     events::{EventConfig, Launcher, LlmpRestartingEventManager, SendExiting as _},
     executors::{DiffExecutor, ExitKind, InProcessExecutor},
     feedback_or_fast,
-    feedbacks::{
-        CrashFeedback, DiffExitKindFeedback, DiffFeedback, Feedback, MaxMapFeedback,
-        StateInitializer, differential::DiffResult,
-    },
+    feedbacks::{CrashFeedback, DiffExitKindFeedback, MaxMapFeedback},
     fuzzer::{Fuzzer, StdFuzzer},
     inputs::{BytesInput, HasTargetBytes},
     monitors::MultiMonitor,
@@ -22,17 +17,17 @@ use libafl::{
     state::{HasCorpus, StdState},
 };
 use libafl_bolts::{
-    Error, Named, SerdeAny,
+    Error,
     core_affinity::Cores,
     current_nanos,
     ownedref::OwnedRef,
     rands::StdRand,
     shmem::{ShMemProvider, StdShMemProvider},
-    tuples::{Handle, Handled as _, MatchNameRef, tuple_list},
+    tuples::{Handled as _, tuple_list},
 };
 use libafl_fandango_pyo3::{
     fandango::{FandangoInprocessModule, FandangoModuleInitError},
-    libafl::FandangoParseExecutor,
+    libafl::{FandangoAcceptanceDiffFeedback, FandangoParseExecutor},
 };
 
 #[derive(Parser)]
@@ -109,33 +104,16 @@ fn run() -> Result<(), String> {
             OwnedRef::Ref(&is_divisible_by_2_fandango),
         );
 
-        let is_divisible_by_2_diff_feedback = DiffFeedback::new(
-            "result_diff_feedback",
-            &is_divisible_by_2_observer_harness,
-            &is_divisible_by_2_observer_fandango,
-            |a: &RefCellValueObserver<'_, bool>, b: &RefCellValueObserver<'_, u32>| {
-                let a_ref = *a.get_ref();
-                let b_ref = *b.get_ref();
-                let b_is_divisible_by_2 = b_ref != 0;
-                if a_ref == b_is_divisible_by_2 {
-                    DiffResult::Equal
-                } else {
-                    log::warn!("Diff: {a_ref} != {b_is_divisible_by_2}({b_ref})");
-                    DiffResult::Diff
-                }
-            },
-        )?;
-
         let mut feedback = coverage_feedback;
 
+        // Flags inputs where harness and spec disagree, and records which way as testcase metadata
         let mut objective = feedback_or_fast!(
-            LogFeedback::new(
-                is_divisible_by_2_observer_fandango.handle(),
-                is_divisible_by_2_observer_harness.handle()
-            ),
             DiffExitKindFeedback::new(),
             CrashFeedback::new(),
-            is_divisible_by_2_diff_feedback
+            FandangoAcceptanceDiffFeedback::new(
+                is_divisible_by_2_observer_harness.handle(),
+                is_divisible_by_2_observer_fandango.handle()
+            )
         );
 
         let mut state = state.unwrap_or_else(|| {
@@ -253,73 +231,3 @@ fn run() -> Result<(), String> {
 
     Ok(())
 }
-
-struct LogFeedback<'a> {
-    is_divisible_by_2_observer_fandango: Handle<RefCellValueObserver<'a, u32>>,
-    is_divisible_by_2_observer_harness: Handle<RefCellValueObserver<'a, bool>>,
-}
-
-impl<'a> LogFeedback<'a> {
-    pub fn new(
-        is_divisible_by_2_observer_fandango: Handle<RefCellValueObserver<'a, u32>>,
-        is_divisible_by_2_observer_harness: Handle<RefCellValueObserver<'a, bool>>,
-    ) -> Self {
-        Self {
-            is_divisible_by_2_observer_fandango,
-            is_divisible_by_2_observer_harness,
-        }
-    }
-}
-
-impl<'a, EM, OT, S> Feedback<EM, BytesInput, OT, S> for LogFeedback<'a>
-where
-    OT: MatchNameRef,
-{
-    fn is_interesting(
-        &mut self,
-        _state: &mut S,
-        _manager: &mut EM,
-        _input: &BytesInput,
-        _observers: &OT,
-        _exit_kind: &ExitKind,
-    ) -> Result<bool, Error> {
-        Ok(false)
-    }
-
-    fn append_metadata(
-        &mut self,
-        _state: &mut S,
-        _manager: &mut EM,
-        observers: &OT,
-        testcase: &mut Testcase<BytesInput>,
-    ) -> Result<(), Error> {
-        let fan = *observers
-            .get(&self.is_divisible_by_2_observer_fandango)
-            .ok_or(Error::illegal_state(
-                "is_divisible_by_2_observer_fandango not found".to_string(),
-            ))?
-            .get_ref();
-        let harness = *observers
-            .get(&self.is_divisible_by_2_observer_harness)
-            .ok_or(Error::illegal_state(
-                "is_divisible_by_2_observer_harness not found".to_string(),
-            ))?
-            .get_ref();
-        testcase.add_metadata(LogMetadata { fan, harness });
-        Ok(())
-    }
-}
-
-#[derive(SerdeAny, Clone, Debug, Serialize, Deserialize)]
-struct LogMetadata {
-    fan: u32,
-    harness: bool,
-}
-
-impl<'a> Named for LogFeedback<'a> {
-    fn name(&self) -> &Cow<'static, str> {
-        &Cow::Borrowed("LogFeedback")
-    }
-}
-
-impl<'a, S> StateInitializer<S> for LogFeedback<'a> {}
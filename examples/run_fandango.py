@@ -22,6 +22,32 @@ def parse_input(wrapper: FandangoWrapper, input: bytes) -> int:
     return len(list(wrapper.fan.parse(input)))
 
 
+def next_inputs(wrapper: FandangoWrapper, n: int) -> list[bytes]:
+    return [next_input(wrapper) for _ in range(n)]
+
+
+def parse_inputs(wrapper: FandangoWrapper, inputs: list[bytes]) -> list[int]:
+    return [parse_input(wrapper, input) for input in inputs]
+
+
+def _tree_to_tuple(tree: Any) -> tuple[str, bytes | None, list]:
+    if tree.symbol.is_terminal:
+        return (str(tree.symbol), tree.to_bytes(), [])
+    return (str(tree.symbol), None, [_tree_to_tuple(c) for c in tree.children])
+
+
+def next_tree(wrapper: FandangoWrapper) -> tuple[str, bytes | None, list]:
+    return _tree_to_tuple(next(wrapper.generator))
+
+
+def parse_trees(wrapper: FandangoWrapper, input: bytes) -> list[tuple[str, bytes | None, list]]:
+    return [_tree_to_tuple(tree) for tree in wrapper.fan.parse(input)]
+
+
+def generate_from_symbol(wrapper: FandangoWrapper, symbol: str) -> tuple[str, bytes | None, list]:
+    return _tree_to_tuple(wrapper.fan.grammar.fuzz(symbol))
+
+
 if __name__ == "__main__":
     # path relative to this script
     fan_file = os.path.dirname(__file__) + "/even_numbers.fan"
@@ -0,0 +1,84 @@
+//! Standalone Fandango worker.
+//!
+//! ```text
+//! fandango_worker --listen <socket> [--workers <n> [--max-restarts <n>]] [--interface <run_fandango.py>] <spec.fan> [key=value ...]
+//! ```
+//!
+//! serves one pre-warmed Fandango instance on a Unix domain socket for
+//! `FandangoSubprocessModule::connect`. With `--workers`, it instead spawns `n` worker processes
+//! and shares them between all connected clients; each of them is restarted up to `--max-restarts`
+//! times (default 10) after it dies. When spawned through
+//! `FandangoSubprocessBuilder::worker_executable`, it runs as a regular IPC worker instead.
+
+use libafl_fandango_pyo3::fandango::{
+    FandangoRestartPolicy, FandangoSubprocessModule, exit_now_if_ipc_worker_argv,
+    serve_multiplexed, serve_unix_socket,
+};
+
+const USAGE: &str = "usage: fandango_worker --listen <socket> [--workers <n> [--max-restarts <n>]] [--interface <path>] <spec.fan> [key=value ...]";
+
+fn main() {
+    // No-op unless spawned as a worker; also covers builds without the `ipc-worker-ctor` feature.
+    exit_now_if_ipc_worker_argv();
+
+    if let Err(err) = run() {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut socket = None;
+    let mut workers = None;
+    let mut restart_policy = FandangoRestartPolicy::default();
+    let mut interface =
+        concat!(env!("CARGO_MANIFEST_DIR"), "/examples/run_fandango.py").to_string();
+    let mut positional = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => socket = Some(args.next().ok_or(USAGE)?),
+            "--interface" => interface = args.next().ok_or(USAGE)?,
+            "--workers" => {
+                let n = args.next().ok_or(USAGE)?;
+                workers = Some(
+                    n.parse::<usize>()
+                        .map_err(|e| format!("invalid --workers `{n}`: {e}\n{USAGE}"))?,
+                );
+            }
+            "--max-restarts" => {
+                let n = args.next().ok_or(USAGE)?;
+                restart_policy.max_restarts = n
+                    .parse::<u32>()
+                    .map_err(|e| format!("invalid --max-restarts `{n}`: {e}\n{USAGE}"))?;
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ => positional.push(arg),
+        }
+    }
+    let socket = socket.ok_or(USAGE)?;
+    let (fandango_file, kwargs) = positional.split_first().ok_or(USAGE)?;
+    let kwargs = kwargs
+        .iter()
+        .map(|kv| {
+            kv.split_once('=')
+                .ok_or_else(|| format!("expected key=value, got `{kv}`\n{USAGE}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match workers {
+        Some(n) => {
+            let builder = FandangoSubprocessModule::builder(fandango_file, &kwargs)
+                .python_interface(&interface)
+                .worker_executable(std::env::current_exe().map_err(|e| e.to_string())?)
+                .restart_policy(Some(restart_policy));
+            serve_multiplexed(&socket, builder, n)
+        }
+        None => serve_unix_socket(&socket, &interface, fandango_file, &kwargs),
+    }
+    .map_err(|e| e.format_report())
+}
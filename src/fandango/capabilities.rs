@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+/// Which optional functions the loaded Python interface exposes.
+///
+/// Calls that need a missing function fail with a Python `AttributeError`; check these flags up
+/// front to pick a fallback instead (e.g. a byte-level mutator when there are no trees).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FandangoCapabilities {
+    /// `next_inputs` and `parse_inputs` (otherwise batches loop over the single-input functions).
+    pub batches: bool,
+    /// `next_tree`, `parse_trees` and `generate_from_symbol`.
+    pub trees: bool,
+    /// `mutate`.
+    pub mutate: bool,
+    /// `repair`.
+    pub repair: bool,
+    /// `stats`.
+    pub stats: bool,
+}
@@ -0,0 +1,257 @@
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::fandango::{
+    DerivationTree, FandangoCallError, FandangoClient, FandangoInprocessModule,
+    FandangoModuleInitError, FandangoSubprocessModule,
+};
+
+/// Which backend [`FandangoConfig::build`] creates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FandangoBackend {
+    /// [`FandangoInprocessModule`].
+    #[default]
+    Inprocess,
+    /// [`FandangoSubprocessModule`].
+    Subprocess,
+}
+
+/// Backend-independent settings for a Fandango client, so a fuzzer can switch between in-process
+/// and subprocess mode with a single field.
+///
+/// Build it in code or deserialize it from a config file; only `spec` is required:
+///
+/// ```toml
+/// spec = "examples/even_numbers.fan"
+/// backend = "subprocess"
+/// seed = 42
+/// call_timeout_ms = 5000
+/// python_path = [".venv/lib/python3.12/site-packages"]
+///
+/// [kwargs]
+/// max_generations = "100"
+/// ```
+///
+/// Backend-specific knobs (resource limits, restart policies, ...) stay on
+/// [`FandangoSubprocessBuilder`](crate::fandango::FandangoSubprocessBuilder).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FandangoConfig {
+    /// Path to the `.fan` spec.
+    pub spec: String,
+    /// Path to the Python interface; `None` uses `examples/run_fandango.py`.
+    #[serde(default)]
+    pub interface: Option<String>,
+    /// Passed to the interface's `setup`.
+    #[serde(default)]
+    pub kwargs: BTreeMap<String, String>,
+    /// Seed for Python's `random` module, applied before `setup`.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Deadline for every call in milliseconds; `None` waits forever.
+    #[serde(default)]
+    pub call_timeout_ms: Option<u64>,
+    /// Directories put in front of Python's module search path, in order.
+    #[serde(default)]
+    pub python_path: Vec<PathBuf>,
+    #[serde(default)]
+    pub backend: FandangoBackend,
+}
+
+impl FandangoConfig {
+    pub fn new(spec: impl Into<String>) -> Self {
+        Self {
+            spec: spec.into(),
+            interface: None,
+            kwargs: BTreeMap::new(),
+            seed: None,
+            call_timeout_ms: None,
+            python_path: Vec::new(),
+            backend: FandangoBackend::default(),
+        }
+    }
+
+    pub fn interface(mut self, path: impl Into<String>) -> Self {
+        self.interface = Some(path.into());
+        self
+    }
+
+    pub fn kwarg(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.kwargs.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Sub-millisecond parts are rounded up; durations beyond `u64::MAX` milliseconds saturate.
+    pub fn call_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.call_timeout_ms =
+            timeout.map(|t| u64::try_from(t.as_nanos().div_ceil(1_000_000)).unwrap_or(u64::MAX));
+        self
+    }
+
+    /// Can be called several times; earlier directories come first.
+    pub fn python_path(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.python_path.push(dir.into());
+        self
+    }
+
+    pub fn backend(mut self, backend: FandangoBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Start the configured backend.
+    ///
+    /// In-process, `python_path` and `seed` apply to the fuzzer's own interpreter and therefore to
+    /// every other in-process module as well. The subprocess backend passes them to the worker
+    /// through its environment.
+    pub fn build(&self) -> Result<FandangoModule, FandangoModuleInitError> {
+        let kwargs: Vec<(&str, &str)> = self
+            .kwargs
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        let call_timeout = self.call_timeout_ms.map(Duration::from_millis);
+        match self.backend {
+            FandangoBackend::Inprocess => {
+                Python::with_gil(|py| {
+                    FandangoInprocessModule::prepend_sys_path(py, &self.python_path)?;
+                    if let Some(seed) = self.seed {
+                        FandangoInprocessModule::seed_python(py, seed)?;
+                    }
+                    Ok::<_, FandangoModuleInitError>(())
+                })?;
+                let module = match &self.interface {
+                    Some(interface) => FandangoInprocessModule::with_custom_python_interface(
+                        interface, &self.spec, &kwargs,
+                    )?,
+                    None => FandangoInprocessModule::new(&self.spec, &kwargs)?,
+                };
+                Ok(FandangoModule::Inprocess(
+                    module.with_call_timeout(call_timeout)?,
+                ))
+            }
+            FandangoBackend::Subprocess => {
+                let mut builder = FandangoSubprocessModule::builder(&self.spec, &kwargs)
+                    .call_timeout(call_timeout);
+                if let Some(interface) = &self.interface {
+                    builder = builder.python_interface(interface);
+                }
+                if let Some(seed) = self.seed {
+                    builder = builder.seed(seed);
+                }
+                for dir in &self.python_path {
+                    builder = builder.python_path(dir);
+                }
+                Ok(FandangoModule::Subprocess(Box::new(builder.build()?)))
+            }
+        }
+    }
+}
+
+/// A client built by [`FandangoConfig::build`].
+pub enum FandangoModule {
+    Inprocess(FandangoInprocessModule),
+    Subprocess(Box<FandangoSubprocessModule>),
+}
+
+impl FandangoModule {
+    fn client(&mut self) -> &mut dyn FandangoClient {
+        match self {
+            Self::Inprocess(m) => m,
+            Self::Subprocess(m) => m.as_mut(),
+        }
+    }
+}
+
+impl FandangoClient for FandangoModule {
+    fn next_input(&mut self) -> Result<Vec<u8>, FandangoCallError> {
+        self.client().next_input()
+    }
+
+    fn parse_input(&mut self, input: &[u8]) -> Result<u32, FandangoCallError> {
+        self.client().parse_input(input)
+    }
+
+    fn next_tree(&mut self) -> Result<DerivationTree, FandangoCallError> {
+        self.client().next_tree()
+    }
+
+    fn parse_trees(&mut self, input: &[u8]) -> Result<Vec<DerivationTree>, FandangoCallError> {
+        self.client().parse_trees(input)
+    }
+
+    fn generate_from_symbol(&mut self, symbol: &str) -> Result<DerivationTree, FandangoCallError> {
+        self.client().generate_from_symbol(symbol)
+    }
+
+    fn next_inputs(&mut self, n: usize) -> Result<Vec<Vec<u8>>, FandangoCallError> {
+        self.client().next_inputs(n)
+    }
+
+    fn parse_inputs(&mut self, inputs: &[&[u8]]) -> Result<Vec<u32>, FandangoCallError> {
+        self.client().parse_inputs(inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_round_trips_through_json() {
+        let config = FandangoConfig::new("examples/even_numbers.fan")
+            .backend(FandangoBackend::Subprocess)
+            .seed(42)
+            .call_timeout(Some(Duration::from_secs(5)))
+            .python_path(".venv/lib/python3.12/site-packages")
+            .kwarg("max_generations", "100");
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(
+            serde_json::from_str::<FandangoConfig>(&json).unwrap(),
+            config
+        );
+
+        let parsed: FandangoConfig = serde_json::from_str(
+            r#"{
+                "spec": "examples/even_numbers.fan",
+                "backend": "subprocess",
+                "seed": 42,
+                "call_timeout_ms": 5000,
+                "python_path": [".venv/lib/python3.12/site-packages"],
+                "kwargs": {"max_generations": "100"}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(parsed, config);
+    }
+
+    #[test]
+    fn config_defaults_to_inprocess_and_rejects_unknown_fields() {
+        let parsed: FandangoConfig = serde_json::from_str(r#"{"spec": "a.fan"}"#).unwrap();
+        assert_eq!(parsed, FandangoConfig::new("a.fan"));
+        assert_eq!(parsed.backend, FandangoBackend::Inprocess);
+
+        let err = serde_json::from_str::<FandangoConfig>(r#"{"spec": "a.fan", "timeout": 5}"#)
+            .unwrap_err();
+        assert!(err.to_string().contains("unknown field `timeout`"), "{err}");
+    }
+
+    #[test]
+    fn call_timeout_rounds_up_and_saturates() {
+        let ms = |t| {
+            FandangoConfig::new("a.fan")
+                .call_timeout(Some(t))
+                .call_timeout_ms
+        };
+        assert_eq!(ms(Duration::from_micros(1500)), Some(2));
+        assert_eq!(ms(Duration::MAX), Some(u64::MAX));
+    }
+}
@@ -0,0 +1,103 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Failure of a single [`FandangoClient`](crate::fandango::FandangoClient) call.
+///
+/// Serializable so the IPC worker can forward the same structured error it would have produced
+/// in process.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FandangoCallError {
+    /// The Python interface raised an exception.
+    PythonException {
+        /// Qualified name of the exception type, e.g. `ValueError`.
+        type_name: String,
+        message: String,
+        /// Output of `traceback.format_exception`, if it could be produced.
+        traceback: Option<String>,
+    },
+    /// The interface raised `StopIteration`: Fandango's generator has no more solutions.
+    Exhausted,
+    /// The interface returned a value that could not be converted to the expected Rust type.
+    WrongReturnType {
+        function: String,
+        expected: String,
+        message: String,
+    },
+    /// The IPC worker is gone (crashed, killed, or shut down).
+    WorkerDied {
+        reason: String,
+        exit_code: Option<i32>,
+        signal: Option<i32>,
+        /// Crash report directory, if the module has a forensics directory configured.
+        forensics: Option<PathBuf>,
+    },
+    /// The call did not complete within its deadline.
+    Timeout(Duration),
+    /// An earlier call was interrupted at its deadline and may have left the Python state
+    /// inconsistent; rebuild the client.
+    Poisoned,
+    /// The IPC stream could not be written, decoded, or contained an unexpected reply.
+    Protocol(String),
+    /// The client does not implement this method (named here), e.g. a custom
+    /// [`FandangoClient`](crate::fandango::FandangoClient) without derivation trees.
+    Unsupported(String),
+}
+
+impl std::fmt::Display for FandangoCallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PythonException {
+                type_name,
+                message,
+                traceback,
+            } => match traceback.as_deref().map(str::trim) {
+                Some(tb) if !tb.is_empty() => write!(f, "{type_name}: {message}\n\n{tb}"),
+                _ => write!(f, "{type_name}: {message}"),
+            },
+            Self::Exhausted => write!(f, "Fandango generator is exhausted (StopIteration)"),
+            Self::WrongReturnType {
+                function,
+                expected,
+                message,
+            } => write!(f, "{function} did not return {expected}: {message}"),
+            Self::WorkerDied {
+                reason,
+                exit_code,
+                signal,
+                forensics,
+            } => {
+                write!(f, "IPC: {reason}")?;
+                if let Some(code) = exit_code {
+                    write!(f, " (exit code {code})")?;
+                }
+                if let Some(sig) = signal {
+                    write!(f, " (signal {sig})")?;
+                }
+                if let Some(dir) = forensics {
+                    write!(f, " (crash report in {})", dir.display())?;
+                }
+                Ok(())
+            }
+            Self::Timeout(d) => write!(f, "Fandango call timed out after {d:?}"),
+            Self::Poisoned => write!(
+                f,
+                "Fandango client is poisoned by an interrupted call and must be rebuilt"
+            ),
+            Self::Protocol(msg) => write!(f, "IPC protocol error: {msg}"),
+            Self::Unsupported(method) => write!(f, "{method} is not supported by this client"),
+        }
+    }
+}
+
+impl std::error::Error for FandangoCallError {}
+
+impl From<FandangoCallError> for libafl::Error {
+    fn from(e: FandangoCallError) -> Self {
+        match e {
+            FandangoCallError::Exhausted => libafl::Error::empty(format!("Fandango error: {e}")),
+            e => libafl::Error::illegal_state(format!("Fandango error: {e}")),
+        }
+    }
+}
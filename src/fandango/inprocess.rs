@@ -1,11 +1,21 @@
 use std::{
     ffi::CString,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
-use pyo3::{exceptions::PyModuleNotFoundError, prelude::*, types::PyDict};
+use log::warn;
 
-use crate::fandango::{FandangoClient, FandangoModuleInitError};
+use pyo3::{
+    exceptions::{PyModuleNotFoundError, PyStopIteration},
+    prelude::*,
+    types::{PyBytes, PyDict, PyList},
+};
+
+use crate::fandango::{
+    DerivationTree, FandangoCallError, FandangoCapabilities, FandangoClient,
+    FandangoModuleInitError, watchdog::Watchdog,
+};
 
 /// A module for running Fandango in process.
 ///
@@ -13,6 +23,11 @@ use crate::fandango::{FandangoClient, FandangoModuleInitError};
 pub struct FandangoInprocessModule {
     module: Py<PyModule>,
     generator: Py<PyAny>,
+    call_timeout: Option<Duration>,
+    /// Started with the first call timeout.
+    watchdog: Option<Watchdog>,
+    /// Set when a call was interrupted; every later call fails with [`FandangoCallError::Poisoned`].
+    poisoned: bool,
 }
 
 impl FandangoInprocessModule {
@@ -28,7 +43,7 @@ impl FandangoInprocessModule {
         Some(lines.concat())
     }
 
-    fn map_py_init_error(py: Python<'_>, err: PyErr) -> FandangoModuleInitError {
+    pub(crate) fn map_py_init_error(py: Python<'_>, err: PyErr) -> FandangoModuleInitError {
         let tb = Self::format_py_traceback(py, &err);
         if err
             .matches(py, py.get_type::<PyModuleNotFoundError>())
@@ -75,10 +90,117 @@ impl FandangoInprocessModule {
                 &wrapped_kwargs,
             )?;
 
-            Ok(Self { module, generator })
+            Ok(Self {
+                module,
+                generator,
+                call_timeout: None,
+                watchdog: None,
+                poisoned: false,
+            })
         })
     }
 
+    /// Set a deadline for every [`FandangoClient`] call (builder style). See [`Self::set_call_timeout`].
+    pub fn with_call_timeout(
+        mut self,
+        timeout: Option<Duration>,
+    ) -> Result<Self, FandangoModuleInitError> {
+        self.set_call_timeout(timeout)?;
+        Ok(self)
+    }
+
+    /// Set a deadline for every [`FandangoClient`] call; `None` (the default) waits forever.
+    ///
+    /// A watchdog thread raises `KeyboardInterrupt` in the calling thread once the deadline has
+    /// passed, and the call returns [`FandangoCallError::Timeout`]. The interrupt is only noticed
+    /// between Python bytecodes, so a call stuck inside native code keeps running until it gets
+    /// back to Python. Since the generator may have been interrupted halfway through an update, the
+    /// module is poisoned afterwards (see [`Self::is_poisoned`]) and has to be rebuilt.
+    ///
+    /// Fails with [`FandangoModuleInitError::Io`] if the watchdog thread cannot be started; the
+    /// previous timeout then stays in effect.
+    pub fn set_call_timeout(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<(), FandangoModuleInitError> {
+        if timeout.is_some() && self.watchdog.is_none() {
+            self.watchdog = Some(Watchdog::new().map_err(FandangoModuleInitError::Io)?);
+        }
+        self.call_timeout = timeout;
+        Ok(())
+    }
+
+    pub fn call_timeout(&self) -> Option<Duration> {
+        self.call_timeout
+    }
+
+    /// Whether a call was interrupted by its timeout; all further calls then fail with
+    /// [`FandangoCallError::Poisoned`].
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    /// Run one interface call under the GIL, enforcing the call timeout if one is set.
+    fn call<T>(
+        &mut self,
+        f: impl FnOnce(Python<'_>, &Py<PyModule>, &Py<PyAny>) -> Result<T, FandangoCallError>,
+    ) -> Result<T, FandangoCallError> {
+        if self.poisoned {
+            return Err(FandangoCallError::Poisoned);
+        }
+        let (module, generator) = (&self.module, &self.generator);
+        let (Some(timeout), Some(watchdog)) = (self.call_timeout, self.watchdog.as_ref()) else {
+            return Python::with_gil(|py| f(py, module, generator));
+        };
+        let (ret, interrupted) =
+            Python::with_gil(|py| watchdog.call(py, timeout, |py| f(py, module, generator)));
+        match ret {
+            // A call that finished (or swallowed the interrupt) in time keeps its result.
+            Err(_) if interrupted => {
+                warn!("fandango: call interrupted after {timeout:?}; the module is now poisoned");
+                self.poisoned = true;
+                Err(FandangoCallError::Timeout(timeout))
+            }
+            ret => ret,
+        }
+    }
+
+    /// Seed Python's `random` module. Shared by [`FandangoConfig`](crate::fandango::FandangoConfig)
+    /// and the IPC worker, which both call it before `setup`.
+    pub(crate) fn seed_python(py: Python<'_>, seed: u64) -> Result<(), FandangoModuleInitError> {
+        py.import("random")
+            .and_then(|random| random.call_method1("seed", (seed,)))
+            .map_err(|err| Self::map_py_init_error(py, err))?;
+        Ok(())
+    }
+
+    /// Put `dirs` in front of `sys.path`, keeping their order. Directories already on `sys.path`
+    /// are left where they are, so building several modules does not pile up duplicates.
+    pub(crate) fn prepend_sys_path(
+        py: Python<'_>,
+        dirs: &[PathBuf],
+    ) -> Result<(), FandangoModuleInitError> {
+        let sys_path = py
+            .import("sys")
+            .and_then(|sys| sys.getattr("path"))
+            .map_err(|err| Self::map_py_init_error(py, err))?;
+        let mut inserted = 0;
+        for dir in dirs {
+            let dir = dir.as_os_str();
+            if sys_path
+                .contains(dir)
+                .map_err(|err| Self::map_py_init_error(py, err))?
+            {
+                continue;
+            }
+            sys_path
+                .call_method1("insert", (inserted, dir))
+                .map_err(|err| Self::map_py_init_error(py, err))?;
+            inserted += 1;
+        }
+        Ok(())
+    }
+
     /// Shared by in-process use and the out-of-process IPC worker.
     pub(crate) fn load_interface_and_setup(
         py: Python<'_>,
@@ -110,6 +232,212 @@ impl FandangoInprocessModule {
         Ok((module, generator))
     }
 
+    /// Which optional functions the loaded interface exposes.
+    pub fn capabilities(&self) -> FandangoCapabilities {
+        Python::with_gil(|py| Self::probe_capabilities(py, &self.module))
+    }
+
+    /// Shared by in-process use and the IPC worker's handshake.
+    pub(crate) fn probe_capabilities(
+        py: Python<'_>,
+        module: &Py<PyModule>,
+    ) -> FandangoCapabilities {
+        let module = module.bind(py);
+        let has = |name: &str| module.hasattr(name).unwrap_or(false);
+        FandangoCapabilities {
+            batches: has("next_inputs") && has("parse_inputs"),
+            trees: has("next_tree") && has("parse_trees") && has("generate_from_symbol"),
+            mutate: has("mutate"),
+            repair: has("repair"),
+            stats: has("stats"),
+        }
+    }
+
+    /// Convert an exception raised by an interface function into a [`FandangoCallError`].
+    pub(crate) fn map_py_call_error(py: Python<'_>, err: PyErr) -> FandangoCallError {
+        if err.is_instance_of::<PyStopIteration>(py) {
+            return FandangoCallError::Exhausted;
+        }
+        let type_name = err
+            .get_type(py)
+            .qualname()
+            .map(|n| n.to_string())
+            .unwrap_or_else(|_| "<unknown exception>".to_string());
+        FandangoCallError::PythonException {
+            type_name,
+            message: err.value(py).to_string(),
+            traceback: Self::format_py_traceback(py, &err),
+        }
+    }
+
+    fn extract_return<'py, T: FromPyObject<'py>>(
+        ret: &Bound<'py, PyAny>,
+        function: &str,
+        expected: &str,
+    ) -> Result<T, FandangoCallError> {
+        ret.extract::<T>()
+            .map_err(|e| FandangoCallError::WrongReturnType {
+                function: function.to_string(),
+                expected: expected.to_string(),
+                message: e.to_string(),
+            })
+    }
+
+    /// Calls `next_input(wrapper)`. Shared by in-process use and the out-of-process IPC worker.
+    pub(crate) fn call_next_input(
+        py: Python<'_>,
+        module: &Py<PyModule>,
+        generator: &Py<PyAny>,
+    ) -> Result<Vec<u8>, FandangoCallError> {
+        let ret = module
+            .bind(py)
+            .getattr("next_input")
+            .and_then(|f| f.call1((generator.bind(py),)))
+            .map_err(|e| Self::map_py_call_error(py, e))?;
+        Self::extract_return(&ret, "next_input", "bytes")
+    }
+
+    /// Calls `parse_input(wrapper, input)`. Shared by in-process use and the out-of-process IPC worker.
+    pub(crate) fn call_parse_input(
+        py: Python<'_>,
+        module: &Py<PyModule>,
+        generator: &Py<PyAny>,
+        input: &[u8],
+    ) -> Result<u32, FandangoCallError> {
+        let ret = module
+            .bind(py)
+            .getattr("parse_input")
+            .and_then(|f| f.call1((generator.bind(py), input)))
+            .map_err(|e| Self::map_py_call_error(py, e))?;
+        Self::extract_return(&ret, "parse_input", "a non-negative int")
+    }
+
+    /// Calls `next_inputs(wrapper, n)` if the interface defines it, otherwise loops over
+    /// `next_input` without releasing the GIL.
+    pub(crate) fn call_next_inputs(
+        py: Python<'_>,
+        module: &Py<PyModule>,
+        generator: &Py<PyAny>,
+        n: usize,
+    ) -> Result<Vec<Vec<u8>>, FandangoCallError> {
+        if !module.bind(py).hasattr("next_inputs").unwrap_or(false) {
+            return (0..n)
+                .map(|_| Self::call_next_input(py, module, generator))
+                .collect();
+        }
+        let ret = module
+            .bind(py)
+            .getattr("next_inputs")
+            .and_then(|f| f.call1((generator.bind(py), n)))
+            .map_err(|e| Self::map_py_call_error(py, e))?;
+        Self::extract_return(&ret, "next_inputs", "a list of bytes")
+    }
+
+    /// Calls `parse_inputs(wrapper, inputs)` if the interface defines it, otherwise loops over
+    /// `parse_input` without releasing the GIL.
+    pub(crate) fn call_parse_inputs(
+        py: Python<'_>,
+        module: &Py<PyModule>,
+        generator: &Py<PyAny>,
+        inputs: &[&[u8]],
+    ) -> Result<Vec<u32>, FandangoCallError> {
+        if !module.bind(py).hasattr("parse_inputs").unwrap_or(false) {
+            return inputs
+                .iter()
+                .map(|i| Self::call_parse_input(py, module, generator, i))
+                .collect();
+        }
+        let ret = PyList::new(py, inputs.iter().map(|i| PyBytes::new(py, i)))
+            .and_then(|list| {
+                module
+                    .bind(py)
+                    .getattr("parse_inputs")?
+                    .call1((generator.bind(py), list))
+            })
+            .map_err(|e| Self::map_py_call_error(py, e))?;
+        Self::extract_return(&ret, "parse_inputs", "a list of non-negative ints")
+    }
+
+    /// Calls `teardown(wrapper)` if the interface defines it. Shared by in-process use (on drop)
+    /// and the out-of-process IPC worker (on shutdown).
+    pub(crate) fn call_teardown(
+        py: Python<'_>,
+        module: &Py<PyModule>,
+        generator: &Py<PyAny>,
+    ) -> Result<(), FandangoCallError> {
+        let module = module.bind(py);
+        if !module.hasattr("teardown").unwrap_or(false) {
+            return Ok(());
+        }
+        module
+            .getattr("teardown")
+            .and_then(|f| f.call1((generator.bind(py),)))
+            .map_err(|e| Self::map_py_call_error(py, e))?;
+        Ok(())
+    }
+
+    /// Calls `next_tree(wrapper)`. Shared by in-process use and the out-of-process IPC worker.
+    pub(crate) fn call_next_tree(
+        py: Python<'_>,
+        module: &Py<PyModule>,
+        generator: &Py<PyAny>,
+    ) -> Result<DerivationTree, FandangoCallError> {
+        let ret = module
+            .bind(py)
+            .getattr("next_tree")
+            .and_then(|f| f.call1((generator.bind(py),)))
+            .map_err(|e| Self::map_py_call_error(py, e))?;
+        DerivationTree::from_py(&ret).map_err(|e| FandangoCallError::WrongReturnType {
+            function: "next_tree".to_string(),
+            expected: "a (symbol, terminal, children) tuple".to_string(),
+            message: e.to_string(),
+        })
+    }
+
+    /// Calls `generate_from_symbol(wrapper, symbol)`. Shared by in-process use and the out-of-process IPC worker.
+    pub(crate) fn call_generate_from_symbol(
+        py: Python<'_>,
+        module: &Py<PyModule>,
+        generator: &Py<PyAny>,
+        symbol: &str,
+    ) -> Result<DerivationTree, FandangoCallError> {
+        let ret = module
+            .bind(py)
+            .getattr("generate_from_symbol")
+            .and_then(|f| f.call1((generator.bind(py), symbol)))
+            .map_err(|e| Self::map_py_call_error(py, e))?;
+        DerivationTree::from_py(&ret).map_err(|e| FandangoCallError::WrongReturnType {
+            function: "generate_from_symbol".to_string(),
+            expected: "a (symbol, terminal, children) tuple".to_string(),
+            message: e.to_string(),
+        })
+    }
+
+    /// Calls `parse_trees(wrapper, input)`. Shared by in-process use and the out-of-process IPC worker.
+    pub(crate) fn call_parse_trees(
+        py: Python<'_>,
+        module: &Py<PyModule>,
+        generator: &Py<PyAny>,
+        input: &[u8],
+    ) -> Result<Vec<DerivationTree>, FandangoCallError> {
+        let ret = module
+            .bind(py)
+            .getattr("parse_trees")
+            .and_then(|f| f.call1((generator.bind(py), input)))
+            .map_err(|e| Self::map_py_call_error(py, e))?;
+        ret.try_iter()
+            .and_then(|trees| {
+                trees
+                    .map(|t| DerivationTree::from_py(&t?))
+                    .collect::<PyResult<Vec<_>>>()
+            })
+            .map_err(|e| FandangoCallError::WrongReturnType {
+                function: "parse_trees".to_string(),
+                expected: "an iterable of (symbol, terminal, children) tuples".to_string(),
+                message: e.to_string(),
+            })
+    }
+
     fn read_code(path: &str) -> Result<CString, FandangoModuleInitError> {
         let code = std::fs::read_to_string(path).map_err(|e| {
             FandangoModuleInitError::ReadFileError(format!("Could not read file: {}", e))
@@ -171,25 +499,45 @@ impl FandangoInprocessModule {
 }
 
 impl FandangoClient for FandangoInprocessModule {
-    fn next_input(&mut self) -> Result<Vec<u8>, String> {
-        Python::with_gil(|py| {
-            let generator = self.generator.clone_ref(py);
-            self.module
-                .getattr(py, "next_input")?
-                .call1(py, (generator,))?
-                .extract::<Vec<u8>>(py)
-        })
-        .map_err(|e| e.to_string())
+    fn next_input(&mut self) -> Result<Vec<u8>, FandangoCallError> {
+        self.call(Self::call_next_input)
     }
 
-    fn parse_input(&mut self, input: &[u8]) -> Result<u32, String> {
-        Python::with_gil(|py| {
-            let generator = self.generator.clone_ref(py);
-            self.module
-                .getattr(py, "parse_input")?
-                .call1(py, (generator, input))?
-                .extract::<u32>(py)
+    fn parse_input(&mut self, input: &[u8]) -> Result<u32, FandangoCallError> {
+        self.call(|py, module, generator| Self::call_parse_input(py, module, generator, input))
+    }
+
+    fn next_tree(&mut self) -> Result<DerivationTree, FandangoCallError> {
+        self.call(Self::call_next_tree)
+    }
+
+    fn parse_trees(&mut self, input: &[u8]) -> Result<Vec<DerivationTree>, FandangoCallError> {
+        self.call(|py, module, generator| Self::call_parse_trees(py, module, generator, input))
+    }
+
+    fn generate_from_symbol(&mut self, symbol: &str) -> Result<DerivationTree, FandangoCallError> {
+        self.call(|py, module, generator| {
+            Self::call_generate_from_symbol(py, module, generator, symbol)
         })
-        .map_err(|e| e.to_string())
+    }
+
+    fn next_inputs(&mut self, n: usize) -> Result<Vec<Vec<u8>>, FandangoCallError> {
+        self.call(|py, module, generator| Self::call_next_inputs(py, module, generator, n))
+    }
+
+    fn parse_inputs(&mut self, inputs: &[&[u8]]) -> Result<Vec<u32>, FandangoCallError> {
+        self.call(|py, module, generator| Self::call_parse_inputs(py, module, generator, inputs))
+    }
+}
+
+impl Drop for FandangoInprocessModule {
+    /// Runs the interface's optional `teardown(wrapper)`, under the call timeout if one is set.
+    /// Skipped when the module is poisoned.
+    fn drop(&mut self) {
+        if let Err(e) = self.call(Self::call_teardown)
+            && !matches!(e, FandangoCallError::Poisoned)
+        {
+            warn!("fandango: teardown failed: {e}");
+        }
     }
 }
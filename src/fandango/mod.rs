@@ -3,11 +3,28 @@ use std::ffi::NulError;
 use pyo3::PyErr;
 use pyo3::prelude::*;
 
+pub(crate) mod capabilities;
+pub(crate) mod config;
+pub(crate) mod error;
 pub(crate) mod inprocess;
+pub(crate) mod prefetch;
+pub(crate) mod server;
 pub(crate) mod subprocess;
+pub(crate) mod tree;
+pub(crate) mod watchdog;
 
+pub use capabilities::FandangoCapabilities;
+pub use config::{FandangoBackend, FandangoConfig, FandangoModule};
+pub use error::FandangoCallError;
 pub use inprocess::FandangoInprocessModule;
-pub use subprocess::{FandangoSubprocessInitIpc, FandangoSubprocessModule};
+pub use prefetch::FandangoPrefetchClient;
+pub use server::serve_multiplexed;
+pub use subprocess::{
+    FandangoRecyclePolicy, FandangoRestartPolicy, FandangoShutdownPolicy,
+    FandangoSubprocessBuilder, FandangoSubprocessInitIpc, FandangoSubprocessModule,
+    FandangoWorkerHealth, FandangoWorkerOutput, exit_now_if_ipc_worker_argv, serve_unix_socket,
+};
+pub use tree::DerivationTree;
 
 #[deprecated(
     since = "0.4.0",
@@ -18,8 +35,44 @@ pub type FandangoPythonModule = FandangoInprocessModule;
 pub type FandangoPythonModuleInitError = FandangoModuleInitError;
 
 pub trait FandangoClient {
-    fn next_input(&mut self) -> Result<Vec<u8>, String>;
-    fn parse_input(&mut self, input: &[u8]) -> Result<u32, String>;
+    fn next_input(&mut self) -> Result<Vec<u8>, FandangoCallError>;
+    fn parse_input(&mut self, input: &[u8]) -> Result<u32, FandangoCallError>;
+
+    /// Generate an input and return its full derivation tree (requires `next_tree` in the interface).
+    ///
+    /// The default implementation fails with [`FandangoCallError::Unsupported`], as do the other
+    /// tree methods; both backends override them.
+    fn next_tree(&mut self) -> Result<DerivationTree, FandangoCallError> {
+        Err(FandangoCallError::Unsupported("next_tree".to_string()))
+    }
+
+    /// Return every derivation tree for `input`; empty if the spec rejects it (requires `parse_trees` in the interface).
+    fn parse_trees(&mut self, _input: &[u8]) -> Result<Vec<DerivationTree>, FandangoCallError> {
+        Err(FandangoCallError::Unsupported("parse_trees".to_string()))
+    }
+
+    /// Generate a fresh expansion of the nonterminal `symbol` (e.g. `<digit>`), requires
+    /// `generate_from_symbol` in the interface. Spans of the result start at 0.
+    fn generate_from_symbol(&mut self, _symbol: &str) -> Result<DerivationTree, FandangoCallError> {
+        Err(FandangoCallError::Unsupported(
+            "generate_from_symbol".to_string(),
+        ))
+    }
+
+    /// Generate `n` inputs at once.
+    ///
+    /// The default implementation calls [`FandangoClient::next_input`] `n` times. Backends
+    /// override this to amortize per-call overhead (GIL acquisition, IPC round trip) over the batch.
+    fn next_inputs(&mut self, n: usize) -> Result<Vec<Vec<u8>>, FandangoCallError> {
+        (0..n).map(|_| self.next_input()).collect()
+    }
+
+    /// Parse several inputs at once; the result has one entry per input, in order.
+    ///
+    /// The default implementation calls [`FandangoClient::parse_input`] for each input.
+    fn parse_inputs(&mut self, inputs: &[&[u8]]) -> Result<Vec<u32>, FandangoCallError> {
+        inputs.iter().map(|i| self.parse_input(i)).collect()
+    }
 }
 
 #[derive(Debug)]
@@ -31,6 +84,8 @@ pub enum FandangoModuleInitError {
     EncodingError(NulError),
     /// Only [`FandangoSubprocessModule`](subprocess::FandangoSubprocessModule); see [`FandangoSubprocessInitIpc`].
     SubprocessIpc(FandangoSubprocessInitIpc),
+    /// A thread the module needs (e.g. the in-process call-timeout watchdog) could not be started.
+    Io(std::io::Error),
 }
 
 impl FandangoModuleInitError {
@@ -48,6 +103,7 @@ impl FandangoModuleInitError {
             Self::ReadFileError(s) => s.clone(),
             Self::EncodingError(e) => format!("Invalid string data (embedded NUL): {e}"),
             Self::SubprocessIpc(e) => e.to_string(),
+            Self::Io(e) => format!("I/O error: {e}"),
         }
     }
 
@@ -78,6 +134,7 @@ impl std::error::Error for FandangoModuleInitError {
             Self::PyErr(e, _) | Self::ModuleNotFoundError(e, _) => e.source(),
             Self::FilePathError(_) | Self::ReadFileError(_) => None,
             Self::SubprocessIpc(e) => std::error::Error::source(e),
+            Self::Io(e) => Some(e),
         }
     }
 }
@@ -0,0 +1,415 @@
+//! Runs any [`FandangoClient`] on a dedicated thread that keeps a bounded queue of generated inputs
+//! filled, so the fuzzer can pop an input while Fandango is already working on the next one.
+//!
+//! All other requests (parsing, trees) are forwarded to the same thread and take priority over
+//! generation. Since the wrapper is used through `&mut self`, at most one caller waits for replies
+//! at a time, so replies are always returned in request order.
+//!
+//! A terminal generation error (exhausted, poisoned, timed out) is queued once and pauses
+//! prefetching; later inputs are requested on demand until one succeeds again.
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use log::warn;
+
+use crate::fandango::{DerivationTree, FandangoCallError, FandangoClient};
+
+enum PrefetchRequest {
+    NextInput,
+    Parse(Vec<u8>),
+    NextTree,
+    ParseTrees(Vec<u8>),
+    GenerateFromSymbol(String),
+}
+
+enum PrefetchReply {
+    Input(Result<Vec<u8>, FandangoCallError>),
+    Parse(Result<u32, FandangoCallError>),
+    Tree(Result<DerivationTree, FandangoCallError>),
+    Trees(Result<Vec<DerivationTree>, FandangoCallError>),
+}
+
+#[derive(Default)]
+struct PrefetchState {
+    ready: VecDeque<Result<Vec<u8>, FandangoCallError>>,
+    requests: VecDeque<PrefetchRequest>,
+    replies: VecDeque<PrefetchReply>,
+    /// Set after a terminal generation error; cleared by the next successful on-demand input.
+    paused: bool,
+    /// Set by the owner to ask the worker thread to exit.
+    shutdown: bool,
+    /// Set by the worker thread when it exits (including by panic).
+    stopped: bool,
+}
+
+struct PrefetchShared {
+    state: Mutex<PrefetchState>,
+    cond: Condvar,
+}
+
+impl PrefetchShared {
+    fn lock(&self) -> MutexGuard<'_, PrefetchState> {
+        // A panic in the worker can only poison the mutex between two plain queue operations,
+        // so the state is still consistent.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn wait<'a>(&self, guard: MutexGuard<'a, PrefetchState>) -> MutexGuard<'a, PrefetchState> {
+        self.cond.wait(guard).unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Marks the worker as stopped when the thread exits, so waiting callers get an error instead of
+/// blocking forever.
+struct StoppedGuard<'a>(&'a PrefetchShared);
+
+impl Drop for StoppedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.lock().stopped = true;
+        self.0.cond.notify_all();
+    }
+}
+
+/// A [`FandangoClient`] wrapper that generates inputs ahead of time on a background thread.
+///
+/// Wrap either backend (e.g. [`FandangoSubprocessModule`](crate::fandango::FandangoSubprocessModule))
+/// and hand the result to [`FandangoGenerator`](crate::libafl::FandangoGenerator) or
+/// [`FandangoPostMutationalStage`](crate::libafl::FandangoPostMutationalStage) to overlap Fandango's
+/// search with target execution.
+pub struct FandangoPrefetchClient<F: FandangoClient + Send + 'static> {
+    shared: Arc<PrefetchShared>,
+    worker: Option<JoinHandle<F>>,
+}
+
+impl<F: FandangoClient + Send + 'static> FandangoPrefetchClient<F> {
+    /// Move `client` to a new thread that keeps up to `capacity` generated inputs ready.
+    pub fn new(client: F, capacity: usize) -> io::Result<Self> {
+        let shared = Arc::new(PrefetchShared {
+            state: Mutex::new(PrefetchState::default()),
+            cond: Condvar::new(),
+        });
+        let capacity = capacity.max(1);
+        let worker_shared = Arc::clone(&shared);
+        let worker = thread::Builder::new()
+            .name("fandango-prefetch".to_string())
+            .spawn(move || Self::run_worker(client, capacity, &worker_shared))?;
+        Ok(Self {
+            shared,
+            worker: Some(worker),
+        })
+    }
+
+    /// Stop the background thread and return the wrapped client. Inputs still in the queue are dropped.
+    pub fn into_inner(mut self) -> Result<F, String> {
+        self.stop_worker()
+    }
+
+    /// Number of generated inputs currently waiting in the queue.
+    pub fn ready_len(&self) -> usize {
+        self.shared.lock().ready.len()
+    }
+
+    fn run_worker(mut client: F, capacity: usize, shared: &PrefetchShared) -> F {
+        let _stopped = StoppedGuard(shared);
+        loop {
+            let mut state = shared.lock();
+            while !state.shutdown
+                && state.requests.is_empty()
+                && (state.paused || state.ready.len() >= capacity)
+            {
+                state = shared.wait(state);
+            }
+            if state.shutdown {
+                return client;
+            }
+            if let Some(req) = state.requests.pop_front() {
+                drop(state);
+                let reply = match req {
+                    PrefetchRequest::NextInput => {
+                        let res = client.next_input();
+                        if res.is_ok() {
+                            shared.lock().paused = false;
+                        }
+                        PrefetchReply::Input(res)
+                    }
+                    PrefetchRequest::Parse(input) => {
+                        PrefetchReply::Parse(client.parse_input(&input))
+                    }
+                    PrefetchRequest::NextTree => PrefetchReply::Tree(client.next_tree()),
+                    PrefetchRequest::GenerateFromSymbol(symbol) => {
+                        PrefetchReply::Tree(client.generate_from_symbol(&symbol))
+                    }
+                    PrefetchRequest::ParseTrees(input) => {
+                        PrefetchReply::Trees(client.parse_trees(&input))
+                    }
+                };
+                shared.lock().replies.push_back(reply);
+            } else {
+                drop(state);
+                let res = client.next_input();
+                let mut state = shared.lock();
+                state.paused = res.as_ref().is_err_and(is_terminal);
+                state.ready.push_back(res);
+            }
+            shared.cond.notify_all();
+        }
+    }
+
+    fn stop_worker(&mut self) -> Result<F, String> {
+        let worker = self
+            .worker
+            .take()
+            .ok_or_else(|| "prefetch worker already stopped".to_string())?;
+        self.shared.lock().shutdown = true;
+        self.shared.cond.notify_all();
+        worker
+            .join()
+            .map_err(|_| "prefetch worker thread panicked".to_string())
+    }
+
+    fn stopped_error() -> FandangoCallError {
+        FandangoCallError::WorkerDied {
+            reason: "prefetch worker thread stopped".to_string(),
+            exit_code: None,
+            signal: None,
+            forensics: None,
+        }
+    }
+
+    /// Queue `requests` for the worker thread and wait for all of their replies, in order.
+    fn roundtrip(
+        &mut self,
+        requests: Vec<PrefetchRequest>,
+    ) -> Result<Vec<PrefetchReply>, FandangoCallError> {
+        let count = requests.len();
+        let mut state = self.shared.lock();
+        state.requests.extend(requests);
+        self.shared.cond.notify_all();
+
+        let mut replies = Vec::with_capacity(count);
+        while replies.len() < count {
+            if let Some(reply) = state.replies.pop_front() {
+                replies.push(reply);
+                continue;
+            }
+            if state.stopped {
+                state.requests.clear();
+                return Err(Self::stopped_error());
+            }
+            state = self.shared.wait(state);
+        }
+        Ok(replies)
+    }
+
+    fn pop_ready(&mut self) -> Result<Vec<u8>, FandangoCallError> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(res) = state.ready.pop_front() {
+                drop(state);
+                self.shared.cond.notify_all();
+                return res;
+            }
+            if state.stopped {
+                return Err(Self::stopped_error());
+            }
+            if state.paused {
+                drop(state);
+                return match self.roundtrip(vec![PrefetchRequest::NextInput])?.pop() {
+                    Some(PrefetchReply::Input(res)) => res,
+                    _ => unreachable!("NextInput is answered with an Input reply"),
+                };
+            }
+            state = self.shared.wait(state);
+        }
+    }
+}
+
+/// Errors after which calling `next_input` again right away cannot be expected to succeed.
+fn is_terminal(e: &FandangoCallError) -> bool {
+    matches!(
+        e,
+        FandangoCallError::Exhausted | FandangoCallError::Poisoned | FandangoCallError::Timeout(_)
+    )
+}
+
+impl<F: FandangoClient + Send + 'static> FandangoClient for FandangoPrefetchClient<F> {
+    fn next_input(&mut self) -> Result<Vec<u8>, FandangoCallError> {
+        self.pop_ready()
+    }
+
+    fn parse_input(&mut self, input: &[u8]) -> Result<u32, FandangoCallError> {
+        self.parse_inputs(&[input])
+            .map(|mut counts| counts.pop().expect("one result per request"))
+    }
+
+    fn next_tree(&mut self) -> Result<DerivationTree, FandangoCallError> {
+        match self.roundtrip(vec![PrefetchRequest::NextTree])?.pop() {
+            Some(PrefetchReply::Tree(res)) => res,
+            _ => unreachable!("NextTree is answered with a Tree reply"),
+        }
+    }
+
+    fn generate_from_symbol(&mut self, symbol: &str) -> Result<DerivationTree, FandangoCallError> {
+        match self
+            .roundtrip(vec![PrefetchRequest::GenerateFromSymbol(
+                symbol.to_string(),
+            )])?
+            .pop()
+        {
+            Some(PrefetchReply::Tree(res)) => res,
+            _ => unreachable!("GenerateFromSymbol is answered with a Tree reply"),
+        }
+    }
+
+    fn parse_trees(&mut self, input: &[u8]) -> Result<Vec<DerivationTree>, FandangoCallError> {
+        match self
+            .roundtrip(vec![PrefetchRequest::ParseTrees(input.to_vec())])?
+            .pop()
+        {
+            Some(PrefetchReply::Trees(res)) => res,
+            _ => unreachable!("ParseTrees is answered with a Trees reply"),
+        }
+    }
+
+    fn parse_inputs(&mut self, inputs: &[&[u8]]) -> Result<Vec<u32>, FandangoCallError> {
+        let requests = inputs
+            .iter()
+            .map(|i| PrefetchRequest::Parse(i.to_vec()))
+            .collect();
+        self.roundtrip(requests)?
+            .into_iter()
+            .map(|reply| match reply {
+                PrefetchReply::Parse(res) => res,
+                _ => unreachable!("Parse is answered with a Parse reply"),
+            })
+            .collect()
+    }
+}
+
+impl<F: FandangoClient + Send + 'static> Drop for FandangoPrefetchClient<F> {
+    fn drop(&mut self) {
+        if self.worker.is_none() {
+            return;
+        }
+        if let Err(e) = self.stop_worker() {
+            warn!("fandango_prefetch: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Counts up from 1 until `limit`, then reports exhaustion; parses inputs back to numbers.
+    struct Counting {
+        calls: Arc<AtomicU64>,
+        limit: u64,
+    }
+
+    impl Counting {
+        fn new(limit: u64) -> (Self, Arc<AtomicU64>) {
+            let calls = Arc::new(AtomicU64::new(0));
+            let client = Self {
+                calls: Arc::clone(&calls),
+                limit,
+            };
+            (client, calls)
+        }
+    }
+
+    impl FandangoClient for Counting {
+        fn next_input(&mut self) -> Result<Vec<u8>, FandangoCallError> {
+            let n = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            thread::sleep(Duration::from_millis(1));
+            if n > self.limit {
+                return Err(FandangoCallError::Exhausted);
+            }
+            Ok(n.to_string().into_bytes())
+        }
+
+        fn parse_input(&mut self, input: &[u8]) -> Result<u32, FandangoCallError> {
+            thread::sleep(Duration::from_millis(1));
+            Ok(std::str::from_utf8(input).unwrap().parse().unwrap())
+        }
+    }
+
+    fn settle() {
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    #[test]
+    fn parse_replies_come_back_in_request_order() {
+        let (client, _) = Counting::new(u64::MAX);
+        let mut prefetch = FandangoPrefetchClient::new(client, 4).unwrap();
+        let inputs = (0..50u32).map(|i| i.to_string()).collect::<Vec<_>>();
+        let inputs = inputs.iter().map(String::as_bytes).collect::<Vec<_>>();
+        for round in 0..3 {
+            assert_eq!(
+                prefetch.next_input().unwrap(),
+                (round + 1).to_string().as_bytes()
+            );
+            assert_eq!(
+                prefetch.parse_inputs(&inputs).unwrap(),
+                (0..50).collect::<Vec<_>>()
+            );
+            assert_eq!(prefetch.parse_input(b"7").unwrap(), 7);
+        }
+    }
+
+    #[test]
+    fn ready_queue_stays_within_capacity() {
+        let (client, calls) = Counting::new(u64::MAX);
+        let mut prefetch = FandangoPrefetchClient::new(client, 3).unwrap();
+        for consumed in 0..5 {
+            settle();
+            assert_eq!(prefetch.ready_len(), 3);
+            assert_eq!(calls.load(Ordering::SeqCst), consumed + 3);
+            prefetch.next_input().unwrap();
+        }
+    }
+
+    #[test]
+    fn into_inner_returns_the_client() {
+        let (client, calls) = Counting::new(u64::MAX);
+        let mut prefetch = FandangoPrefetchClient::new(client, 2).unwrap();
+        assert_eq!(prefetch.next_input().unwrap(), b"1");
+        let client = prefetch.into_inner().unwrap();
+        assert!(Arc::ptr_eq(&client.calls, &calls));
+        let generated = calls.load(Ordering::SeqCst);
+        settle();
+        assert_eq!(calls.load(Ordering::SeqCst), generated);
+    }
+
+    #[test]
+    fn terminal_error_stops_prefetching() {
+        let (client, calls) = Counting::new(2);
+        let mut prefetch = FandangoPrefetchClient::new(client, 8).unwrap();
+        assert_eq!(prefetch.next_input().unwrap(), b"1");
+        assert_eq!(prefetch.next_input().unwrap(), b"2");
+        settle();
+        // The error is queued once and nothing is generated after it.
+        assert_eq!(prefetch.ready_len(), 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(matches!(
+            prefetch.next_input(),
+            Err(FandangoCallError::Exhausted)
+        ));
+        settle();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        // Later calls ask the client again, one at a time.
+        assert!(matches!(
+            prefetch.next_input(),
+            Err(FandangoCallError::Exhausted)
+        ));
+        settle();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert_eq!(prefetch.ready_len(), 0);
+    }
+}
@@ -0,0 +1,284 @@
+//! A Fandango server that shares a pool of subprocess workers between many fuzzer clients.
+//!
+//! Clients connect with [`FandangoSubprocessModule::connect`] and speak the regular worker
+//! protocol. Every client gets its own request queue; idle workers pick the next request
+//! round-robin across clients, so a client sending large batches cannot starve the others.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+
+use log::{error, info, warn};
+
+use crate::fandango::subprocess::{
+    FandangoSubprocessBuilder, FandangoSubprocessInitIpc, FandangoSubprocessModule, IpcFrame,
+    IpcHandshake, IpcReq, IpcResp, read_msg, write_msg,
+};
+use crate::fandango::{FandangoCapabilities, FandangoModuleInitError};
+
+type ClientWriter = Arc<Mutex<io::BufWriter<UnixStream>>>;
+
+/// A request waiting for a worker, and where to send the reply.
+struct Job {
+    id: u64,
+    req: IpcReq,
+    out: ClientWriter,
+}
+
+#[derive(Default)]
+struct Queues {
+    /// Pending requests per connected client.
+    clients: BTreeMap<u64, VecDeque<Job>>,
+    /// Client served most recently; the next pick starts after it.
+    last_served: u64,
+    /// Dispatchers still taking jobs.
+    workers: usize,
+}
+
+impl Queues {
+    /// Pop the oldest request of the next client (in round-robin order) that has one.
+    fn pop_fair(&mut self) -> Option<Job> {
+        let last = self.last_served;
+        let client = self
+            .clients
+            .range(last + 1..)
+            .chain(self.clients.range(..=last))
+            .find(|(_, q)| !q.is_empty())
+            .map(|(&c, _)| c)?;
+        self.last_served = client;
+        self.clients.get_mut(&client)?.pop_front()
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    queues: Mutex<Queues>,
+    cond: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queues> {
+        // Only plain queue operations happen under the lock, so a poisoned state is still consistent.
+        self.queues.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn next_job(&self) -> Job {
+        let mut queues = self.lock();
+        loop {
+            if let Some(job) = queues.pop_fair() {
+                return job;
+            }
+            queues = self.cond.wait(queues).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Take a dispatcher out of the pool and return how many are left, unless it is the last one:
+    /// that one stays and answers every request with an error, so clients do not wait forever.
+    fn leave_pool(&self) -> Option<usize> {
+        let mut queues = self.lock();
+        if queues.workers <= 1 {
+            return None;
+        }
+        queues.workers -= 1;
+        Some(queues.workers)
+    }
+}
+
+/// Spawn `num_workers` workers configured by `workers` and serve them to any number of clients on a
+/// Unix domain socket.
+///
+/// All workers are started (and their spec parsed) before the socket is bound, so clients connect
+/// to a warm pool. Each worker keeps the timeout and restart policy of the builder; a worker that
+/// died and cannot be restarted leaves the pool, and the remaining ones take over its share of the
+/// requests. A stale socket file at `socket_path` is replaced. Only returns on startup errors.
+pub fn serve_multiplexed(
+    socket_path: impl AsRef<Path>,
+    workers: FandangoSubprocessBuilder,
+    num_workers: usize,
+) -> Result<(), FandangoModuleInitError> {
+    let socket_path = socket_path.as_ref();
+    let pool = (0..num_workers.max(1))
+        .map(|_| workers.clone().build())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let capabilities = pool[0].capabilities();
+    let shared = Arc::new(Shared::default());
+    shared.lock().workers = pool.len();
+    let io_err = |e| FandangoModuleInitError::SubprocessIpc(FandangoSubprocessInitIpc::Io(e));
+    for (i, worker) in pool.into_iter().enumerate() {
+        let shared = Arc::clone(&shared);
+        thread::Builder::new()
+            .name(format!("fandango-server-worker-{i}"))
+            .spawn(move || run_dispatcher(i, worker, &shared))
+            .map_err(io_err)?;
+    }
+
+    if std::fs::symlink_metadata(socket_path).is_ok_and(|m| m.file_type().is_socket()) {
+        std::fs::remove_file(socket_path).map_err(io_err)?;
+    }
+    let listener = UnixListener::bind(socket_path).map_err(io_err)?;
+    info!(
+        "fandango_ipc: serving {} workers on {}",
+        num_workers.max(1),
+        socket_path.display()
+    );
+
+    for (client, stream) in (1..).zip(listener.incoming()) {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("fandango_ipc: accept failed: {e}");
+                continue;
+            }
+        };
+        let shared = Arc::clone(&shared);
+        let spawned = thread::Builder::new()
+            .name(format!("fandango-server-client-{client}"))
+            .spawn(move || {
+                if let Err(e) = read_client(client, stream, capabilities, &shared) {
+                    warn!("fandango_ipc: client {client} closed with error: {e}");
+                }
+                shared.lock().clients.remove(&client);
+            });
+        if let Err(e) = spawned {
+            warn!("fandango_ipc: could not spawn client thread: {e}");
+        }
+    }
+    Ok(())
+}
+
+/// Queue every request of one client until it disconnects.
+fn read_client(
+    client: u64,
+    stream: UnixStream,
+    capabilities: FandangoCapabilities,
+    shared: &Shared,
+) -> Result<(), String> {
+    let out: ClientWriter = Arc::new(Mutex::new(io::BufWriter::new(
+        stream.try_clone().map_err(|e| e.to_string())?,
+    )));
+    IpcHandshake::Ready(capabilities).send(&mut *lock_writer(&out))?;
+    shared.lock().clients.insert(client, VecDeque::new());
+
+    let mut inp = io::BufReader::new(stream);
+    while let Some(IpcFrame { id, body }) = read_msg::<_, IpcFrame<IpcReq>>(&mut inp)? {
+        if let IpcReq::Shutdown = body {
+            // The workers are shared, so a client leaving must not tear them down.
+            let reply = IpcFrame {
+                id,
+                body: IpcResp::ShutdownOk,
+            };
+            return write_msg(&mut *lock_writer(&out), &reply);
+        }
+        let job = Job {
+            id,
+            req: body,
+            out: Arc::clone(&out),
+        };
+        shared
+            .lock()
+            .clients
+            .entry(client)
+            .or_default()
+            .push_back(job);
+        shared.cond.notify_one();
+    }
+    Ok(())
+}
+
+fn run_dispatcher(index: usize, mut worker: FandangoSubprocessModule, shared: &Shared) {
+    let mut last_standing = false;
+    loop {
+        let Job { id, req, out } = shared.next_job();
+        let body = worker.rpc(&req).unwrap_or_else(IpcResp::CallErr);
+        // The client may be gone by now; its reader thread reports that.
+        let _ = write_msg(&mut *lock_writer(&out), &IpcFrame { id, body });
+
+        if last_standing || !worker.is_beyond_restart() {
+            continue;
+        }
+        match shared.leave_pool() {
+            Some(left) => {
+                error!(
+                    "fandango_ipc: pool worker {index} died and cannot be restarted; \
+                     removed it from the pool ({left} left)"
+                );
+                return;
+            }
+            None => {
+                error!(
+                    "fandango_ipc: the last pool worker ({index}) died and cannot be restarted; \
+                     every request will fail from now on"
+                );
+                last_standing = true;
+            }
+        }
+    }
+}
+
+fn lock_writer(out: &ClientWriter) -> MutexGuard<'_, io::BufWriter<UnixStream>> {
+    out.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Queue `ids` for `client`; the id encodes the client so the pop order is easy to read.
+    fn push(queues: &mut Queues, out: &ClientWriter, client: u64, ids: &[u64]) {
+        let queue = queues.clients.entry(client).or_default();
+        for &id in ids {
+            queue.push_back(Job {
+                id,
+                req: IpcReq::Next,
+                out: Arc::clone(out),
+            });
+        }
+    }
+
+    fn pop_all(queues: &mut Queues) -> Vec<u64> {
+        std::iter::from_fn(|| queues.pop_fair().map(|job| job.id)).collect()
+    }
+
+    fn writer() -> ClientWriter {
+        let (sock, _) = UnixStream::pair().unwrap();
+        Arc::new(Mutex::new(io::BufWriter::new(sock)))
+    }
+
+    #[test]
+    fn pop_fair_round_robin() {
+        let out = writer();
+        let mut queues = Queues::default();
+        push(&mut queues, &out, 1, &[10, 11, 12]);
+        push(&mut queues, &out, 2, &[20, 21]);
+        push(&mut queues, &out, 3, &[30]);
+        assert_eq!(pop_all(&mut queues), [10, 20, 30, 11, 21, 12]);
+    }
+
+    #[test]
+    fn pop_fair_wraps_around_after_last_served() {
+        let out = writer();
+        let mut queues = Queues::default();
+        push(&mut queues, &out, 1, &[10, 11]);
+        push(&mut queues, &out, 2, &[20]);
+        push(&mut queues, &out, 3, &[30, 31]);
+        queues.last_served = 2;
+        assert_eq!(pop_all(&mut queues), [30, 10, 20, 31, 11]);
+    }
+
+    #[test]
+    fn pop_fair_skips_empty_queues() {
+        let out = writer();
+        let mut queues = Queues::default();
+        push(&mut queues, &out, 1, &[10]);
+        push(&mut queues, &out, 2, &[]);
+        push(&mut queues, &out, 4, &[40, 41]);
+        assert_eq!(pop_all(&mut queues), [10, 40, 41]);
+        assert!(queues.pop_fair().is_none());
+        assert_eq!(queues.last_served, 4);
+    }
+}
@@ -1,23 +1,35 @@
 //! Python runs only in a **child** (`Command::current_exe()`), so OOM/SIGKILL there returns an error here instead of killing the fuzzer.
 //! A `#[ctor]` at the bottom of this file detects worker `argv` and runs the loop **before `main`**.
 //!
-//! IPC is **length-prefixed `postcard`** over stdin/stdout (see `read_msg` / `write_msg`); no hand-maintained tag bytes.
+//! IPC is **length-prefixed `postcard`** over a socketpair inherited as fd 3 (see `read_msg` / `write_msg`); no hand-maintained
+//! tag bytes. The worker's stdout/stderr stay free for Python, so a stray `print()` cannot corrupt the frame stream.
 
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::io::{self, Read, Write};
-use std::path::PathBuf;
-use std::process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio};
+use std::net::Shutdown;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, mpsc};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+#[cfg(feature = "ipc-worker-ctor")]
 use ctor::ctor;
-use log::{error, warn};
+use log::{error, info, warn};
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 
-use crate::fandango::{FandangoClient, FandangoInprocessModule, FandangoModuleInitError};
+use crate::fandango::{
+    DerivationTree, FandangoCallError, FandangoCapabilities, FandangoClient,
+    FandangoInprocessModule, FandangoModuleInitError,
+};
 
 fn subprocess_init(i: FandangoSubprocessInitIpc) -> FandangoModuleInitError {
     FandangoModuleInitError::SubprocessIpc(i)
@@ -30,6 +42,11 @@ pub enum FandangoSubprocessInitIpc {
     KwargsJson(serde_json::Error),
     MissingPipe(&'static str),
     HandshakeFailed(String),
+    /// The worker speaks a different IPC protocol version, e.g. a stale standalone worker binary.
+    VersionMismatch {
+        worker: u32,
+        ours: u32,
+    },
     /// Python setup failed in the worker (same text as [`FandangoPythonModuleInitError::format_report`] would produce there).
     WorkerSetupFailed(String),
 }
@@ -41,6 +58,10 @@ impl std::fmt::Display for FandangoSubprocessInitIpc {
             Self::KwargsJson(e) => write!(f, "could not serialize kwargs to JSON: {e}"),
             Self::MissingPipe(what) => write!(f, "IPC worker missing {what} pipe"),
             Self::HandshakeFailed(msg) => write!(f, "{msg}"),
+            Self::VersionMismatch { worker, ours } => write!(
+                f,
+                "IPC protocol version mismatch: worker speaks {worker}, this build speaks {ours}; rebuild the worker binary"
+            ),
             Self::WorkerSetupFailed(msg) => write!(f, "{msg}"),
         }
     }
@@ -51,7 +72,10 @@ impl std::error::Error for FandangoSubprocessInitIpc {
         match self {
             Self::Io(e) => Some(e),
             Self::KwargsJson(e) => Some(e),
-            Self::MissingPipe(_) | Self::HandshakeFailed(_) | Self::WorkerSetupFailed(_) => None,
+            Self::MissingPipe(_)
+            | Self::HandshakeFailed(_)
+            | Self::VersionMismatch { .. }
+            | Self::WorkerSetupFailed(_) => None,
         }
     }
 }
@@ -59,32 +83,106 @@ impl std::error::Error for FandangoSubprocessInitIpc {
 /// `argv[1]` when this executable is the IPC worker.
 pub const IPC_WORKER_ARG: &str = "__libafl_fandango_ipc_worker__";
 
+/// Seed for Python's `random` module, set on the worker by [`FandangoSubprocessBuilder::seed`].
+const IPC_SEED_ENV: &str = "LIBAFL_FANDANGO_SEED";
+
+/// `argv[1]` when this executable is a zygote that forks IPC workers.
+const IPC_ZYGOTE_ARG: &str = "__libafl_fandango_ipc_zygote__";
+
+/// File descriptor the worker finds its end of the IPC socketpair on.
+const IPC_WORKER_FD: RawFd = 3;
+
 /// Reject absurd frames (misbehaving peer / corruption).
 const MAX_FRAME_BYTES: u32 = 1024 * 1024 * 1024;
 
-/// After closing stdin, wait this long for the worker to exit before `SIGKILL` ([`Drop`] path).
-const DROP_GRACEFUL_WAIT: Duration = Duration::from_millis(750);
+/// Frame id of the [`IpcReq::Shutdown`] sent when retiring a worker, distinct from any request id.
+const SHUTDOWN_ID: u64 = u64::MAX;
+
+/// Bump whenever a message type changes. Sent as a bare `u32` frame before [`IpcHandshake`], so a
+/// mismatch is detected before anything version-specific is decoded.
+pub(crate) const IPC_PROTOCOL_VERSION: u32 = 5;
 
 #[derive(Serialize, Deserialize)]
-enum IpcHandshake {
-    Ready,
+pub(crate) enum IpcHandshake {
+    Ready(FandangoCapabilities),
     Failed(String),
 }
 
+impl IpcHandshake {
+    /// Write the version frame followed by the handshake.
+    pub(crate) fn send<W: Write>(&self, w: &mut W) -> Result<(), String> {
+        write_msg(w, &IPC_PROTOCOL_VERSION)?;
+        write_msg(w, self)
+    }
+}
+
+/// A request or response on the wire; a response carries the `id` of the request it answers.
 #[derive(Serialize, Deserialize)]
-enum IpcReq {
+pub(crate) struct IpcFrame<T> {
+    pub(crate) id: u64,
+    pub(crate) body: T,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) enum IpcReq {
     Next,
     Parse(Vec<u8>),
+    NextBatch(u64),
+    ParseBatch(Vec<Vec<u8>>),
+    NextTree,
+    ParseTrees(Vec<u8>),
+    GenerateFromSymbol(String),
+    /// Run the interface's `teardown` (if any), reply, and stop serving the connection.
+    Shutdown,
+    /// Report [`FandangoWorkerHealth`]; answered without calling into the interface.
+    Health,
+}
+
+impl IpcReq {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Next => "Next",
+            Self::Parse(_) => "Parse",
+            Self::NextBatch(_) => "NextBatch",
+            Self::ParseBatch(_) => "ParseBatch",
+            Self::NextTree => "NextTree",
+            Self::ParseTrees(_) => "ParseTrees",
+            Self::GenerateFromSymbol(_) => "GenerateFromSymbol",
+            Self::Shutdown => "Shutdown",
+            Self::Health => "Health",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
-enum IpcResp {
+pub(crate) enum IpcResp {
     NextOk(Vec<u8>),
     ParseOk(u32),
-    CallErr(String),
+    NextBatchOk(Vec<Vec<u8>>),
+    ParseBatchOk(Vec<u32>),
+    TreeOk(DerivationTree),
+    TreesOk(Vec<DerivationTree>),
+    ShutdownOk,
+    HealthOk(FandangoWorkerHealth),
+    CallErr(FandangoCallError),
+}
+
+/// What a worker reports about itself, see [`FandangoSubprocessModule::health`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FandangoWorkerHealth {
+    /// Process id of the worker (of the server, when connected to one).
+    pub pid: u32,
+    /// Time since the worker started serving this connection.
+    pub uptime: Duration,
+    /// Requests answered on this connection before the health check.
+    pub requests_served: u64,
+    /// Resident set size in bytes (`VmRSS`), if `/proc` is available.
+    pub rss: Option<u64>,
+    /// `sys.version` of the worker's interpreter.
+    pub python_version: String,
 }
 
-fn write_msg<W: Write, T: Serialize>(w: &mut W, msg: &T) -> Result<(), String> {
+pub(crate) fn write_msg<W: Write, T: Serialize>(w: &mut W, msg: &T) -> Result<(), String> {
     let bytes = postcard::to_stdvec(msg).map_err(|e| e.to_string())?;
     let len: u32 = bytes
         .len()
@@ -96,8 +194,8 @@ fn write_msg<W: Write, T: Serialize>(w: &mut W, msg: &T) -> Result<(), String> {
     Ok(())
 }
 
-/// `Ok(None)` = clean EOF before the next frame (stdin closed).
-fn read_msg<R: Read, T: DeserializeOwned>(r: &mut R) -> Result<Option<T>, String> {
+/// `Ok(None)` = clean EOF before the next frame (peer closed the socket).
+pub(crate) fn read_msg<R: Read, T: DeserializeOwned>(r: &mut R) -> Result<Option<T>, String> {
     let mut lenb = [0u8; 4];
     match r.read_exact(&mut lenb) {
         Ok(()) => {}
@@ -115,6 +213,135 @@ fn read_msg<R: Read, T: DeserializeOwned>(r: &mut R) -> Result<Option<T>, String
         .map(Some)
 }
 
+/// Reader over the IPC socket that fails with [`io::ErrorKind::TimedOut`] once `deadline` has passed.
+///
+/// Sits below the `BufReader`, so buffered bytes are still served without waiting.
+struct DeadlineReader<R> {
+    inner: R,
+    deadline: Option<Instant>,
+    /// Set when a read failed because of the deadline; the frame stream is then out of sync.
+    expired: bool,
+}
+
+impl<R> DeadlineReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            deadline: None,
+            expired: false,
+        }
+    }
+}
+
+impl<R: Read + AsRawFd> Read for DeadlineReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(deadline) = self.deadline
+            && let Err(e) = wait_ready(self.inner.as_raw_fd(), libc::POLLIN, deadline)
+        {
+            self.expired = e.kind() == io::ErrorKind::TimedOut;
+            return Err(e);
+        }
+        self.inner.read(buf)
+    }
+}
+
+/// Writer over the IPC socket that fails with [`io::ErrorKind::TimedOut`] once `deadline` has passed,
+/// e.g. because a hung worker stopped reading and the socket buffer is full.
+///
+/// Sits below the `BufWriter`; the deadline covers the whole frame, not each `write`.
+struct DeadlineWriter<W> {
+    inner: W,
+    deadline: Option<Instant>,
+    /// Set when a write failed because of the deadline; the frame stream is then out of sync.
+    expired: bool,
+}
+
+impl<W> DeadlineWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            deadline: None,
+            expired: false,
+        }
+    }
+}
+
+impl<W: Write + AsRawFd> Write for DeadlineWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(deadline) = self.deadline else {
+            return self.inner.write(buf);
+        };
+        let fd = self.inner.as_raw_fd();
+        loop {
+            if let Err(e) = wait_ready(fd, libc::POLLOUT, deadline) {
+                self.expired = e.kind() == io::ErrorKind::TimedOut;
+                return Err(e);
+            }
+            // Non-blocking: a blocking write of a large buffer would wait for all of it to fit.
+            // SAFETY: `buf` is valid for reads of `buf.len()` bytes.
+            match unsafe {
+                libc::send(
+                    fd,
+                    buf.as_ptr().cast(),
+                    buf.len(),
+                    libc::MSG_DONTWAIT | libc::MSG_NOSIGNAL,
+                )
+            } {
+                -1 => {
+                    let e = io::Error::last_os_error();
+                    if !matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+                    ) {
+                        return Err(e);
+                    }
+                }
+                n => return Ok(n as usize),
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Block until `fd` is ready for `events` (or hung up), or fail with [`io::ErrorKind::TimedOut`] at
+/// `deadline`.
+fn wait_ready(fd: RawFd, events: libc::c_short, deadline: Instant) -> io::Result<()> {
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                if events == libc::POLLOUT {
+                    "IPC worker did not read the request in time"
+                } else {
+                    "IPC worker did not answer in time"
+                },
+            ));
+        }
+        let mut pfd = libc::pollfd {
+            fd,
+            events,
+            revents: 0,
+        };
+        // Round up so we never spin on a sub-millisecond remainder
+        let ms = remaining.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32;
+        // SAFETY: `pfd` is a valid pollfd and we pass exactly one entry.
+        match unsafe { libc::poll(&mut pfd, 1, ms) } {
+            0 => continue,
+            n if n > 0 => return Ok(()),
+            _ => {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
 fn kwargs_json(kwargs: &[(&str, &str)]) -> Result<String, serde_json::Error> {
     serde_json::to_string(&std::collections::HashMap::<_, _>::from_iter(
         kwargs.iter().copied(),
@@ -136,12 +363,18 @@ fn pydict_from_kwargs_json<'py>(py: Python<'py>, json: &str) -> Result<Bound<'py
     Ok(d)
 }
 
-pub(crate) fn exit_now_if_ipc_worker_argv() {
+/// Serve requests as the IPC worker (or zygote) when `argv` asks for it, then exit; returns otherwise.
+///
+/// With the default `ipc-worker-ctor` feature this runs automatically before `main`. Binaries built
+/// without it that want to spawn themselves as a worker call this at the top of `main`.
+pub fn exit_now_if_ipc_worker_argv() {
     let a: Vec<String> = std::env::args().collect();
-    if a.len() != 5 || a[1] != IPC_WORKER_ARG {
-        return;
-    }
-    if let Err(e) = run_worker(&a[2], &a[3], &a[4]) {
+    let res = match (a.len(), a.get(1).map(String::as_str)) {
+        (5, Some(IPC_WORKER_ARG)) => run_worker(&a[2], &a[3], &a[4]),
+        (5, Some(IPC_ZYGOTE_ARG)) => run_zygote(&a[2], &a[3], &a[4]),
+        _ => return,
+    };
+    if let Err(e) = res {
         error!("fandango_ipc worker: {e}");
         eprintln!("fandango-ipc: {e}");
         std::process::exit(1);
@@ -149,264 +382,2384 @@ pub(crate) fn exit_now_if_ipc_worker_argv() {
     std::process::exit(0);
 }
 
-fn run_worker(interface: &str, fan_file: &str, kwargs_json: &str) -> Result<(), String> {
-    let mut out = io::stdout().lock();
-    let mut inp = io::stdin().lock();
+/// Take over the socket the parent passed as [`IPC_WORKER_FD`].
+fn inherited_ipc_socket() -> Result<UnixStream, String> {
+    // SAFETY: the parent dup'ed its end of the socketpair to this fd right before exec, and
+    // nothing else in this process owns it.
+    let sock = unsafe { UnixStream::from_raw_fd(IPC_WORKER_FD) };
+    set_cloexec(IPC_WORKER_FD).map_err(|e| e.to_string())?;
+    Ok(sock)
+}
 
-    Python::with_gil(|py| {
+/// Load the interface and call `setup`; on failure, report it to the parent as the handshake.
+fn setup_worker(
+    sock: &UnixStream,
+    interface: &str,
+    fan_file: &str,
+    kwargs_json: &str,
+) -> Result<(Py<PyModule>, Py<PyAny>), String> {
+    let seed = std::env::var(IPC_SEED_ENV)
+        .ok()
+        .map(|s| s.parse::<u64>())
+        .transpose()
+        .map_err(|e| format!("invalid {IPC_SEED_ENV}: {e}"));
+    let setup = Python::with_gil(|py| {
+        if let Some(seed) = seed? {
+            FandangoInprocessModule::seed_python(py, seed).map_err(|e| e.format_report())?;
+        }
         let kwargs = pydict_from_kwargs_json(py, kwargs_json)?;
-        let (module, generator) = match FandangoInprocessModule::load_interface_and_setup(
-            py, interface, fan_file, &kwargs,
-        ) {
-            Ok(x) => x,
-            Err(e) => {
-                let text = e.format_report();
-                error!("fandango_ipc worker Python setup failed: {text}");
-                write_msg(&mut out, &IpcHandshake::Failed(text.clone()))?;
-                return Err(text);
-            }
-        };
+        FandangoInprocessModule::load_interface_and_setup(py, interface, fan_file, &kwargs)
+            .map_err(|e| e.format_report())
+    });
+    if let Err(text) = &setup {
+        error!("fandango_ipc worker Python setup failed: {text}");
+        IpcHandshake::Failed(text.clone()).send(&mut io::BufWriter::new(sock))?;
+    }
+    setup
+}
 
-        write_msg(&mut out, &IpcHandshake::Ready)?;
-
-        while let Some(req) = read_msg::<_, IpcReq>(&mut inp)? {
-            let resp = match req {
-                IpcReq::Next => {
-                    let res: PyResult<Vec<u8>> = (|| {
-                        let w = generator.clone_ref(py);
-                        module
-                            .getattr(py, "next_input")?
-                            .call1(py, (w,))?
-                            .extract::<Vec<u8>>(py)
-                    })();
-                    match res {
-                        Ok(bytes) => IpcResp::NextOk(bytes),
-                        Err(e) => IpcResp::CallErr(e.to_string()),
-                    }
-                }
-                IpcReq::Parse(buf) => {
-                    let res: PyResult<u32> = (|| {
-                        let w = generator.clone_ref(py);
-                        module
-                            .getattr(py, "parse_input")?
-                            .call1(py, (w, buf.as_slice()))?
-                            .extract::<u32>(py)
-                    })();
-                    match res {
-                        Ok(n) => IpcResp::ParseOk(n),
-                        Err(e) => IpcResp::CallErr(e.to_string()),
-                    }
-                }
-            };
-            write_msg(&mut out, &resp)?;
-        }
-        Ok(())
-    })
+fn run_worker(interface: &str, fan_file: &str, kwargs_json: &str) -> Result<(), String> {
+    let started = Instant::now();
+    let sock = inherited_ipc_socket()?;
+    let interface = Mutex::new(setup_worker(&sock, interface, fan_file, kwargs_json)?);
+    serve_connection(&interface, sock, true, started)
 }
 
-/// A module for running Fandango in a subprocess.
-///
-/// Trades off some speed (due to IPC) for more robust error handling (e.g. if the child process OOMs).
-///
-/// Essentially a wrapper around `FandangoInprocessModule` that runs it in a subprocess and communicates via IPC.
-pub struct FandangoSubprocessModule {
-    child: Option<Child>,
-    stdin: Option<io::BufWriter<ChildStdin>>,
-    stdout: Option<io::BufReader<ChildStdout>>,
+/// Requests from [`FandangoSubprocessModule`] to its zygote, on the zygote's IPC socket.
+#[derive(Serialize, Deserialize)]
+enum ZygoteReq {
+    /// Fork a worker that serves the socket sent right after this request (see [`send_fds`]). With
+    /// `output`, the write ends of its own stdout and stderr pipes follow the socket.
+    Fork { output: bool },
+    /// `waitpid` a forked worker (the zygote is its parent).
+    Wait { pid: u32, nohang: bool },
 }
 
-impl FandangoSubprocessModule {
-    pub fn new(
-        fandango_file: &str,
-        kwargs: &[(&str, &str)],
-    ) -> Result<Self, FandangoModuleInitError> {
-        let iface = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples/run_fandango.py");
-        Self::with_custom_python_interface(
-            iface.to_str().ok_or_else(|| {
-                FandangoModuleInitError::FilePathError(
-                    "default interface path is not UTF-8".to_string(),
-                )
-            })?,
-            fandango_file,
-            kwargs,
-        )
+#[derive(Serialize, Deserialize)]
+enum ZygoteResp {
+    Forked(u32),
+    /// Raw `wait4` status and peak RSS in bytes; `None` if the worker is still running.
+    Status(Option<(i32, u64)>),
+    Failed(String),
+}
+
+/// Set up Python once, then fork a worker for every [`ZygoteReq::Fork`] until the parent closes
+/// the IPC socket.
+fn run_zygote(interface: &str, fan_file: &str, kwargs_json: &str) -> Result<(), String> {
+    let ctl = inherited_ipc_socket()?;
+    let interface = Mutex::new(setup_worker(&ctl, interface, fan_file, kwargs_json)?);
+    let capabilities = Python::with_gil(|py| {
+        FandangoInprocessModule::probe_capabilities(py, &lock_interface(&interface).0)
+    });
+    IpcHandshake::Ready(capabilities).send(&mut &ctl)?;
+
+    while let Some(req) = read_msg::<_, ZygoteReq>(&mut &ctl)? {
+        let resp = match req {
+            ZygoteReq::Fork { output } => match fork_worker(&ctl, output, &interface) {
+                Ok(pid) => ZygoteResp::Forked(pid),
+                Err(e) => ZygoteResp::Failed(e),
+            },
+            ZygoteReq::Wait { pid, nohang } => match reap(pid, nohang) {
+                Ok(status) => ZygoteResp::Status(status),
+                Err(e) => ZygoteResp::Failed(e.to_string()),
+            },
+        };
+        write_msg(&mut &ctl, &resp)?;
     }
+    Ok(())
+}
 
-    pub fn with_custom_python_interface(
-        python_interface_path: &str,
-        fandango_file: &str,
-        kwargs: &[(&str, &str)],
-    ) -> Result<Self, FandangoModuleInitError> {
-        let exe = std::env::current_exe()
-            .map_err(|e| subprocess_init(FandangoSubprocessInitIpc::Io(e)))?;
-        let kw = kwargs_json(kwargs)
-            .map_err(|e| subprocess_init(FandangoSubprocessInitIpc::KwargsJson(e)))?;
-
-        let mut child = Command::new(&exe)
-            .arg(IPC_WORKER_ARG)
-            .arg(python_interface_path)
-            .arg(fandango_file)
-            .arg(&kw)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
-            .spawn()
-            .map_err(|e| subprocess_init(FandangoSubprocessInitIpc::Io(e)))?;
+/// Receive the new worker's socket (and with `output`, its stdout and stderr) from the parent and
+/// fork; the child serves the socket and never returns.
+///
+/// Uses Python's `os.fork` rather than `libc::fork`, so the interpreter's at-fork hooks run (e.g.
+/// `random` reseeds itself, so forked workers do not all generate the same inputs).
+fn fork_worker(
+    ctl: &UnixStream,
+    output: bool,
+    interface: &Mutex<(Py<PyModule>, Py<PyAny>)>,
+) -> Result<u32, String> {
+    let expected = if output { 3 } else { 1 };
+    let mut fds = recv_fds(ctl, expected)
+        .map_err(|e| format!("could not receive the worker's fds: {e}"))?
+        .into_iter();
+    if fds.len() != expected {
+        return Err(format!(
+            "expected {expected} fds for the worker, got {}",
+            fds.len()
+        ));
+    }
+    let stream = UnixStream::from(fds.next().expect("length was checked"));
+    let pid: i32 = Python::with_gil(|py| {
+        // Otherwise text the zygote has buffered would be written again by every worker.
+        let sys = py.import("sys")?;
+        for stream in ["stdout", "stderr"] {
+            let _ = sys.getattr(stream)?.call_method0("flush");
+        }
+        py.import("os")?.call_method0("fork")?.extract()
+    })
+    .map_err(|e: PyErr| format!("os.fork failed: {e}"))?;
+    if pid != 0 {
+        // The zygote's copies of the fds are dropped here, so only the worker holds them.
+        return Ok(pid as u32);
+    }
+    // Close the zygote's control socket in the child, so the parent still sees EOF when the zygote
+    // dies.
+    // SAFETY: the fd stays open in the zygote's copy of the object; this child exits below without
+    // returning to (and dropping) it.
+    unsafe {
+        libc::close(ctl.as_raw_fd());
+    }
+    for (fd, target) in fds.zip([libc::STDOUT_FILENO, libc::STDERR_FILENO]) {
+        // SAFETY: dup2 onto a standard stream; the received fd is closed when `fd` drops.
+        if unsafe { libc::dup2(fd.as_raw_fd(), target) } == -1 {
+            std::process::exit(1);
+        }
+    }
+    let code = match serve_connection(interface, stream, true, Instant::now()) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("fandango_ipc worker: {e}");
+            eprintln!("fandango-ipc: {e}");
+            1
+        }
+    };
+    std::process::exit(code);
+}
 
-        let pid = child.id();
+/// `wait4` that retries on `EINTR`: the raw status and peak RSS (`ru_maxrss`) in bytes, or
+/// `Ok(None)` if `nohang` and the process is still running.
+fn reap(pid: u32, nohang: bool) -> io::Result<Option<(i32, u64)>> {
+    let flags = if nohang { libc::WNOHANG } else { 0 };
+    let mut status = 0;
+    // SAFETY: rusage is plain old data, all zeroes is a valid value.
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: `status` and `usage` are valid out pointers for the duration of the call.
+        match unsafe { libc::wait4(pid as libc::pid_t, &mut status, flags, &mut usage) } {
+            0 => return Ok(None),
+            -1 => {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+            _ => return Ok(Some((status, usage.ru_maxrss.max(0) as u64 * 1024))),
+        }
+    }
+}
 
-        let stdin = io::BufWriter::new(
-            child
-                .stdin
-                .take()
-                .ok_or_else(|| subprocess_init(FandangoSubprocessInitIpc::MissingPipe("stdin")))?,
-        );
-        let mut stdout =
-            io::BufReader::new(child.stdout.take().ok_or_else(|| {
-                subprocess_init(FandangoSubprocessInitIpc::MissingPipe("stdout"))
-            })?);
+/// Send the handshake, then answer requests in order until the peer closes the connection or sends
+/// [`IpcReq::Shutdown`].
+///
+/// Requests are read on a separate thread, so a client with several requests in flight never
+/// blocks on a full socket while we are writing a reply. `interface` holds the module and the
+/// generator returned by `setup`. Its lock is held for every call into them, because Python
+/// releases the GIL in the middle of a call and a generator cannot be resumed by two connections at
+/// once; connections sharing a server are therefore answered one request at a time. With
+/// `teardown` unset (a shared server), `Shutdown` only ends the connection and leaves the
+/// interface's `teardown` alone. `started` is reported as the start of the uptime in
+/// [`IpcReq::Health`].
+fn serve_connection(
+    interface: &Mutex<(Py<PyModule>, Py<PyAny>)>,
+    sock: UnixStream,
+    teardown: bool,
+    started: Instant,
+) -> Result<(), String> {
+    let mut out = io::BufWriter::new(sock.try_clone().map_err(|e| e.to_string())?);
+    let capabilities = Python::with_gil(|py| {
+        FandangoInprocessModule::probe_capabilities(py, &lock_interface(interface).0)
+    });
+    IpcHandshake::Ready(capabilities).send(&mut out)?;
+    let python_version = Python::with_gil(|py| py.version().to_string());
+    let mut served = 0;
 
-        let hs: IpcHandshake = match read_msg(&mut stdout) {
-            Ok(Some(h)) => h,
-            Ok(None) => {
-                return Err(subprocess_init(FandangoSubprocessInitIpc::HandshakeFailed(
-                    ipc_fail(&mut child, "EOF during handshake"),
-                )));
+    let (req_tx, req_rx) = mpsc::channel();
+    let reader = thread::Builder::new()
+        .name("fandango-ipc-reader".to_string())
+        .spawn(move || {
+            let mut inp = io::BufReader::new(sock);
+            loop {
+                let frame = read_msg::<_, IpcFrame<IpcReq>>(&mut inp);
+                let done = !matches!(frame, Ok(Some(_)));
+                if req_tx.send(frame).is_err() || done {
+                    return;
+                }
             }
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut res = Ok(());
+    for frame in req_rx.iter() {
+        let IpcFrame { id, body } = match frame {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break,
             Err(e) => {
-                return Err(subprocess_init(FandangoSubprocessInitIpc::HandshakeFailed(
-                    ipc_fail(&mut child, e),
-                )));
+                res = Err(e);
+                break;
             }
         };
-        match hs {
-            IpcHandshake::Ready => {}
-            IpcHandshake::Failed(msg) => {
-                error!("fandango_ipc: worker setup failed (pid={pid}): {msg}");
-                let _ = child.wait();
-                return Err(subprocess_init(
-                    FandangoSubprocessInitIpc::WorkerSetupFailed(msg),
-                ));
+        let shutdown = matches!(body, IpcReq::Shutdown);
+        let body = match body {
+            IpcReq::Health => IpcResp::HealthOk(FandangoWorkerHealth {
+                pid: std::process::id(),
+                uptime: started.elapsed(),
+                requests_served: served,
+                rss: proc_status_bytes(std::process::id(), "VmRSS"),
+                python_version: python_version.clone(),
+            }),
+            IpcReq::Shutdown if !teardown => IpcResp::ShutdownOk,
+            body => {
+                // Taken before the GIL: a thread waiting for the lock must not hold the GIL the
+                // current holder needs to finish its call.
+                let handles = lock_interface(interface);
+                Python::with_gil(|py| handle_request(py, &handles.0, &handles.1, body))
             }
+        };
+        served += 1;
+        if let Err(e) = write_msg(&mut out, &IpcFrame { id, body }) {
+            res = Err(e);
+            break;
         }
+        if shutdown {
+            break;
+        }
+    }
+    // Unblock the reader if we stopped early because a reply could not be written.
+    let _ = out.get_ref().shutdown(Shutdown::Read);
+    let _ = reader.join();
+    res
+}
 
-        Ok(Self {
-            child: Some(child),
-            stdin: Some(stdin),
-            stdout: Some(stdout),
-        })
+fn lock_interface(
+    interface: &Mutex<(Py<PyModule>, Py<PyAny>)>,
+) -> MutexGuard<'_, (Py<PyModule>, Py<PyAny>)> {
+    // `handle_request` catches Python errors, so a poisoned lock leaves nothing half-done behind.
+    interface.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn handle_request(
+    py: Python<'_>,
+    module: &Py<PyModule>,
+    generator: &Py<PyAny>,
+    req: IpcReq,
+) -> IpcResp {
+    match req {
+        IpcReq::Next => match FandangoInprocessModule::call_next_input(py, module, generator) {
+            Ok(bytes) => IpcResp::NextOk(bytes),
+            Err(e) => IpcResp::CallErr(e),
+        },
+        IpcReq::Parse(buf) => {
+            match FandangoInprocessModule::call_parse_input(py, module, generator, &buf) {
+                Ok(n) => IpcResp::ParseOk(n),
+                Err(e) => IpcResp::CallErr(e),
+            }
+        }
+        IpcReq::NextBatch(n) => {
+            match FandangoInprocessModule::call_next_inputs(py, module, generator, n as usize) {
+                Ok(inputs) => IpcResp::NextBatchOk(inputs),
+                Err(e) => IpcResp::CallErr(e),
+            }
+        }
+        IpcReq::ParseBatch(bufs) => {
+            let bufs: Vec<&[u8]> = bufs.iter().map(Vec::as_slice).collect();
+            match FandangoInprocessModule::call_parse_inputs(py, module, generator, &bufs) {
+                Ok(counts) => IpcResp::ParseBatchOk(counts),
+                Err(e) => IpcResp::CallErr(e),
+            }
+        }
+        IpcReq::NextTree => match FandangoInprocessModule::call_next_tree(py, module, generator) {
+            Ok(tree) => IpcResp::TreeOk(tree),
+            Err(e) => IpcResp::CallErr(e),
+        },
+        IpcReq::GenerateFromSymbol(symbol) => {
+            match FandangoInprocessModule::call_generate_from_symbol(py, module, generator, &symbol)
+            {
+                Ok(tree) => IpcResp::TreeOk(tree),
+                Err(e) => IpcResp::CallErr(e),
+            }
+        }
+        IpcReq::ParseTrees(buf) => {
+            match FandangoInprocessModule::call_parse_trees(py, module, generator, &buf) {
+                Ok(trees) => IpcResp::TreesOk(trees),
+                Err(e) => IpcResp::CallErr(e),
+            }
+        }
+        IpcReq::Shutdown => match FandangoInprocessModule::call_teardown(py, module, generator) {
+            Ok(()) => IpcResp::ShutdownOk,
+            Err(e) => IpcResp::CallErr(e),
+        },
+        IpcReq::Health => unreachable!("answered by serve_connection"),
     }
+}
 
-    /// Close the IPC connection and wait for the worker to exit.
-    ///
-    /// The worker stops its request loop when stdin closes, then exits normally. Prefer this
-    /// over relying on [`Drop`] when you want a clean shutdown without a bounded wait followed by
-    /// `SIGKILL`.
-    pub fn shutdown(&mut self) -> io::Result<ExitStatus> {
-        if self.child.is_none() {
-            return Err(io::Error::new(
-                io::ErrorKind::NotConnected,
-                "Fandango IPC subprocess already shut down",
-            ));
+/// Set up the Python interface once and serve the worker protocol on a Unix domain socket.
+///
+/// Every connection is handled on its own thread and shares the same Fandango instance, so several
+/// fuzzer processes can use one pre-warmed server through [`FandangoSubprocessModule::connect`].
+/// Calls from different connections are serialized, so the server answers one request at a time;
+/// use [`serve_multiplexed`](crate::fandango::serve_multiplexed) with several workers for
+/// parallelism. A stale socket file at `socket_path` is replaced. Only returns on setup or bind
+/// errors.
+pub fn serve_unix_socket(
+    socket_path: impl AsRef<Path>,
+    python_interface_path: &str,
+    fandango_file: &str,
+    kwargs: &[(&str, &str)],
+) -> Result<(), FandangoModuleInitError> {
+    let socket_path = socket_path.as_ref();
+    let handles = Python::with_gil(|py| {
+        let d = PyDict::new(py);
+        for (k, v) in kwargs {
+            d.set_item(k, v)
+                .map_err(|e| FandangoInprocessModule::map_py_init_error(py, e))?;
         }
-        if let Some(s) = self.stdin.as_mut() {
-            let _ = s.flush();
-        }
-        self.stdin.take();
-        self.stdout.take();
-        self.child.take().expect("child was Some").wait()
-    }
-
-    fn rpc(&mut self, req: &IpcReq) -> Result<IpcResp, String> {
-        let child = self
-            .child
-            .as_mut()
-            .ok_or_else(|| "IPC subprocess shut down".to_string())?;
-        let stdin = self
-            .stdin
-            .as_mut()
-            .ok_or_else(|| "IPC subprocess shut down".to_string())?;
-        let stdout = self
-            .stdout
-            .as_mut()
-            .ok_or_else(|| "IPC subprocess shut down".to_string())?;
-        write_msg(stdin, req)?;
-        match read_msg(stdout).map_err(|e| ipc_fail(child, e))? {
-            Some(r) => Ok(r),
-            None => Err(ipc_fail(child, "EOF from worker before response")),
-        }
-    }
-}
-
-fn ipc_fail(child: &mut Child, reason: impl std::fmt::Display) -> String {
-    let st = child.wait().ok().map(|s| s.to_string()).unwrap_or_default();
-    let msg = if st.is_empty() {
-        format!("IPC: {reason}")
-    } else {
-        format!("IPC: {reason} ({st})")
-    };
-    warn!("{msg}");
-    msg
+        FandangoInprocessModule::load_interface_and_setup(
+            py,
+            python_interface_path,
+            fandango_file,
+            &d,
+        )
+    })?;
+    let handles = Arc::new(Mutex::new(handles));
+    let started = Instant::now();
+
+    let io_err = |e| subprocess_init(FandangoSubprocessInitIpc::Io(e));
+    if std::fs::symlink_metadata(socket_path).is_ok_and(|m| m.file_type().is_socket()) {
+        std::fs::remove_file(socket_path).map_err(io_err)?;
+    }
+    let listener = UnixListener::bind(socket_path).map_err(io_err)?;
+    info!("fandango_ipc: serving on {}", socket_path.display());
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("fandango_ipc: accept failed: {e}");
+                continue;
+            }
+        };
+        let handles = Arc::clone(&handles);
+        let spawned = thread::Builder::new()
+            .name("fandango-server-conn".to_string())
+            .spawn(move || {
+                if let Err(e) = serve_connection(&handles, stream, false, started) {
+                    warn!("fandango_ipc: connection closed with error: {e}");
+                }
+            });
+        if let Err(e) = spawned {
+            warn!("fandango_ipc: could not spawn connection thread: {e}");
+        }
+    }
+    Ok(())
 }
 
-impl FandangoClient for FandangoSubprocessModule {
-    fn next_input(&mut self) -> Result<Vec<u8>, String> {
-        match self.rpc(&IpcReq::Next)? {
-            IpcResp::NextOk(b) => Ok(b),
-            IpcResp::CallErr(s) => Err(s),
-            IpcResp::ParseOk(_) => Err("unexpected ParseOk from worker".into()),
+/// When and how often [`FandangoSubprocessModule`] restarts a worker that died.
+///
+/// The call that hit the dead worker still returns its error (the request may be what crashed it);
+/// the next call respawns the worker with the original interface path, spec and kwargs. Restarts
+/// without a successful call in between wait exponentially longer, up to `max_backoff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FandangoRestartPolicy {
+    /// Total number of restarts over the lifetime of the module; afterwards calls keep failing.
+    pub max_restarts: u32,
+    /// Wait before the first restart after a successful call.
+    pub initial_backoff: Duration,
+    /// Upper bound for the doubled wait.
+    pub max_backoff: Duration,
+}
+
+impl Default for FandangoRestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
         }
     }
+}
 
-    fn parse_input(&mut self, input: &[u8]) -> Result<u32, String> {
-        match self.rpc(&IpcReq::Parse(input.to_vec()))? {
-            IpcResp::ParseOk(n) => Ok(n),
-            IpcResp::CallErr(s) => Err(s),
-            IpcResp::NextOk(_) => Err("unexpected NextOk from worker".into()),
+impl FandangoRestartPolicy {
+    fn backoff(&self, consecutive: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(1 << consecutive.min(31))
+            .min(self.max_backoff)
+    }
+}
+
+/// When [`FandangoSubprocessModule`] replaces a healthy worker with a fresh one, to shed memory that
+/// long Python sessions accumulate (populations, caches).
+///
+/// The check runs before each request: requests still in flight are answered by the old worker,
+/// which is shut down gracefully once its replacement has started. Callers only see the added
+/// latency of the restart. If the replacement cannot be started, the old worker keeps serving
+/// until the next threshold: another `max_requests` requests, or the next RSS reading. Connections
+/// to a server are never recycled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FandangoRecyclePolicy {
+    /// Replace the worker after it has received this many requests.
+    pub max_requests: Option<u64>,
+    /// Replace the worker once its resident set size (`VmRSS` in `/proc/<pid>/status`) exceeds
+    /// this many bytes. Read at most once per second, so the worker may overshoot it a little.
+    pub max_rss: Option<u64>,
+}
+
+/// How often [`FandangoRecyclePolicy::max_rss`] reads the worker's RSS.
+const RSS_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How [`FandangoSubprocessModule`] stops a worker when it is dropped or recycled.
+///
+/// The worker first gets an [`IpcReq::Shutdown`] request, so the interface's optional
+/// `teardown(wrapper)` can flush coverage data, statistics or a population. If the worker has not
+/// exited `grace` after that request, it gets `SIGTERM`, and `SIGKILL` another `term_wait` later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FandangoShutdownPolicy {
+    /// Time for `teardown` and a normal exit.
+    pub grace: Duration,
+    /// Time between `SIGTERM` and `SIGKILL`.
+    pub term_wait: Duration,
+}
+
+impl Default for FandangoShutdownPolicy {
+    fn default() -> Self {
+        Self {
+            grace: Duration::from_millis(750),
+            term_wait: Duration::from_millis(250),
         }
     }
 }
 
-impl Drop for FandangoSubprocessModule {
-    fn drop(&mut self) {
-        let Some(mut child) = self.child.take() else {
-            return;
+/// Read a `kB` field such as `VmRSS` from `/proc/<pid>/status`, in bytes.
+pub(crate) fn proc_status_bytes(pid: u32, field: &str) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    status
+        .lines()
+        .find_map(|line| {
+            let kb = line.strip_prefix(field)?.strip_prefix(':')?;
+            kb.trim().strip_suffix("kB")?.trim().parse::<u64>().ok()
+        })
+        .map(|kb| kb * 1024)
+}
+
+/// What happens to the worker's stdout/stderr (the IPC stream uses its own socket).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FandangoWorkerOutput {
+    /// Share the fuzzer's stdout/stderr.
+    #[default]
+    Inherit,
+    /// Discard both streams.
+    Null,
+    /// Forward every line to the `log` crate tagged with the worker pid: stdout at `info`, stderr at `warn`.
+    Log,
+}
+
+impl FandangoWorkerOutput {
+    fn apply(&self, cmd: &mut Command) {
+        let stdio = || match self {
+            Self::Inherit => Stdio::inherit(),
+            Self::Null => Stdio::null(),
+            Self::Log => Stdio::piped(),
         };
-        if let Some(s) = self.stdin.as_mut() {
-            let _ = s.flush();
+        cmd.stdout(stdio()).stderr(stdio());
+    }
+}
+
+/// Lines a [`StderrTail`] keeps for crash reports.
+const STDERR_TAIL_LINES: usize = 64;
+
+/// How long a crash report waits for the last stderr lines of a dead worker to be forwarded.
+const STDERR_TAIL_WAIT: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+struct TailState {
+    lines: VecDeque<String>,
+    /// The stream reached EOF.
+    closed: bool,
+}
+
+/// The last lines a worker wrote to stderr, kept by [`forward_output_to_log`] for crash reports.
+#[derive(Clone, Default)]
+struct StderrTail(Arc<(Mutex<TailState>, Condvar)>);
+
+impl StderrTail {
+    fn lock(&self) -> MutexGuard<'_, TailState> {
+        self.0.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, line: String) {
+        let mut state = self.lock();
+        if state.lines.len() == STDERR_TAIL_LINES {
+            state.lines.pop_front();
         }
-        self.stdin.take();
-        self.stdout.take();
+        state.lines.push_back(line);
+    }
 
-        let pid = child.id();
-        let deadline = Instant::now() + DROP_GRACEFUL_WAIT;
+    fn close(&self) {
+        self.lock().closed = true;
+        self.0.1.notify_all();
+    }
+
+    /// The kept lines, after giving the forwarding thread [`STDERR_TAIL_WAIT`] to reach EOF.
+    fn snapshot(&self) -> Vec<String> {
+        let state = self
+            .0
+            .1
+            .wait_timeout_while(self.lock(), STDERR_TAIL_WAIT, |s| !s.closed)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+        state.lines.iter().cloned().collect()
+    }
+}
+
+/// Spawn one thread per stream that logs the worker's output line by line until EOF.
+fn forward_output_to_log(
+    pid: u32,
+    stdout: impl Read + Send + 'static,
+    stderr: impl Read + Send + 'static,
+    tail: StderrTail,
+) -> io::Result<()> {
+    thread::Builder::new()
+        .name(format!("fandango-worker-{pid}-stdout"))
+        .spawn(move || {
+            for line in io::BufRead::split(io::BufReader::new(stdout), b'\n').map_while(Result::ok)
+            {
+                info!(
+                    "fandango_ipc: worker pid={pid} stdout: {}",
+                    String::from_utf8_lossy(&line)
+                );
+            }
+        })?;
+    thread::Builder::new()
+        .name(format!("fandango-worker-{pid}-stderr"))
+        .spawn(move || {
+            for line in io::BufRead::split(io::BufReader::new(stderr), b'\n').map_while(Result::ok)
+            {
+                let line = String::from_utf8_lossy(&line).into_owned();
+                warn!("fandango_ipc: worker pid={pid} stderr: {line}");
+                tail.push(line);
+            }
+            tail.close();
+        })?;
+    Ok(())
+}
+
+/// Send `fds` over `sock` as `SCM_RIGHTS`, attached to a single byte.
+fn send_fds(sock: &UnixStream, fds: &[RawFd]) -> io::Result<()> {
+    let byte = [0u8];
+    let mut iov = libc::iovec {
+        iov_base: byte.as_ptr() as *mut libc::c_void,
+        iov_len: 1,
+    };
+    let payload = std::mem::size_of_val(fds) as u32;
+    // SAFETY: CMSG_SPACE only computes a size.
+    let space = unsafe { libc::CMSG_SPACE(payload) } as usize;
+    // u64s, so the buffer is aligned for `cmsghdr`.
+    let mut control = vec![0u64; space.div_ceil(8)];
+    // SAFETY: msghdr is plain old data, all zeroes is a valid value.
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = space as _;
+    // SAFETY: `control` has room for one header with `fds.len()` fds, so CMSG_FIRSTHDR is non-null
+    // and the copy stays in bounds; `msg` only points to buffers that outlive the sendmsg call.
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(payload) as _;
+        std::ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg).cast(), fds.len());
         loop {
-            match child.try_wait() {
-                Ok(Some(_)) => return,
-                Ok(None) => {
-                    if Instant::now() >= deadline {
-                        warn!(
-                            "fandango_ipc: worker pid={pid} did not exit after stdin close within {:?}; sending SIGKILL",
-                            DROP_GRACEFUL_WAIT
-                        );
-                        let _ = child.kill();
-                        let _ = child.wait();
-                        return;
-                    }
-                    thread::sleep(Duration::from_millis(5));
+            if libc::sendmsg(sock.as_raw_fd(), &msg, 0) != -1 {
+                return Ok(());
+            }
+            let e = io::Error::last_os_error();
+            if e.kind() != io::ErrorKind::Interrupted {
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// Receive up to `max` fds sent with [`send_fds`].
+fn recv_fds(sock: &UnixStream, max: usize) -> io::Result<Vec<OwnedFd>> {
+    let mut byte = [0u8];
+    let mut iov = libc::iovec {
+        iov_base: byte.as_mut_ptr().cast(),
+        iov_len: 1,
+    };
+    // SAFETY: CMSG_SPACE only computes a size.
+    let space = unsafe { libc::CMSG_SPACE((max * size_of::<RawFd>()) as u32) } as usize;
+    let mut control = vec![0u64; space.div_ceil(8)];
+    // SAFETY: msghdr is plain old data, all zeroes is a valid value.
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = space as _;
+    let n = loop {
+        // SAFETY: `msg` only points to buffers that outlive the call.
+        match unsafe { libc::recvmsg(sock.as_raw_fd(), &mut msg, 0) } {
+            -1 => {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
                 }
-                Err(e) => {
-                    warn!("fandango_ipc: try_wait on pid={pid} failed ({e}); sending SIGKILL");
-                    let _ = child.kill();
-                    let _ = child.wait();
-                    return;
+            }
+            n => break n,
+        }
+    };
+    let mut fds = Vec::new();
+    // SAFETY: the kernel filled in `msg.msg_controllen` bytes of well-formed headers, and every fd
+    // in an SCM_RIGHTS message is new in this process and owned by nobody else.
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg).cast::<RawFd>();
+                let len =
+                    ((*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize) / size_of::<RawFd>();
+                for i in 0..len {
+                    fds.push(OwnedFd::from_raw_fd(data.add(i).read_unaligned()));
                 }
             }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    if n == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+        return Err(io::Error::other("more fds than expected"));
+    }
+    Ok(fds)
+}
+
+fn set_cloexec(fd: RawFd) -> io::Result<()> {
+    // SAFETY: plain fcntl on an fd we own.
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags == -1 || libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) == -1 {
+            return Err(io::Error::last_os_error());
         }
     }
+    Ok(())
 }
 
-#[ctor]
-fn ipc_worker_ctor() {
-    exit_now_if_ipc_worker_argv();
+fn clear_cloexec(fd: RawFd) -> io::Result<()> {
+    // SAFETY: plain fcntl on an fd we own; async-signal-safe, so usable in `pre_exec`.
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags == -1 || libc::fcntl(fd, libc::F_SETFD, flags & !libc::FD_CLOEXEC) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// OS limits applied to the worker between `fork` and `exec`.
+#[derive(Debug, Clone, Copy, Default)]
+struct WorkerLimits {
+    address_space: Option<u64>,
+    cpu_time: Option<Duration>,
+    nice: Option<i32>,
+    new_process_group: bool,
+}
+
+impl WorkerLimits {
+    fn apply(&self, cmd: &mut Command) {
+        if self.new_process_group {
+            cmd.process_group(0);
+        }
+        let limits = *self;
+        if limits.address_space.is_none() && limits.cpu_time.is_none() && limits.nice.is_none() {
+            return;
+        }
+        // SAFETY: the closure only calls async-signal-safe libc functions (`setrlimit`, `setpriority`)
+        // and does not allocate.
+        unsafe {
+            cmd.pre_exec(move || {
+                if let Some(bytes) = limits.address_space {
+                    set_rlimit(libc::RLIMIT_AS, bytes)?;
+                }
+                if let Some(cpu) = limits.cpu_time {
+                    // Whole seconds only; round up so a non-zero limit never becomes 0 (unlimited).
+                    let secs = cpu.as_secs() + u64::from(cpu.subsec_nanos() > 0);
+                    set_rlimit(libc::RLIMIT_CPU, secs)?;
+                }
+                if let Some(nice) = limits.nice
+                    && libc::setpriority(libc::PRIO_PROCESS, 0, nice) == -1
+                {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+type RlimitResource = libc::c_int;
+
+fn set_rlimit(resource: RlimitResource, value: u64) -> io::Result<()> {
+    let lim = libc::rlimit {
+        rlim_cur: value as libc::rlim_t,
+        rlim_max: value as libc::rlim_t,
+    };
+    // SAFETY: `lim` is a valid rlimit for the duration of the call.
+    if unsafe { libc::setrlimit(resource, &lim) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Everything needed to (re)start a worker.
+struct WorkerSpec {
+    /// Executable to run as the worker; `None` re-executes the current binary.
+    exe: Option<PathBuf>,
+    python_interface_path: String,
+    fandango_file: String,
+    kwargs_json: String,
+    limits: WorkerLimits,
+    output: FandangoWorkerOutput,
+    seed: Option<u64>,
+    /// Prepended to the worker's `PYTHONPATH`.
+    python_path: Vec<PathBuf>,
+}
+
+/// Where [`FandangoSubprocessModule`] gets its worker from, initially and on restart.
+enum WorkerSource {
+    Spawn(WorkerSpec),
+    /// Fork workers from a zygote, which is started (again) from `spec` when there is none.
+    Zygote {
+        spec: WorkerSpec,
+        zygote: Option<Arc<Mutex<Zygote>>>,
+    },
+    /// A server started with [`serve_unix_socket`] (e.g. the `fandango_worker` binary).
+    Connect(PathBuf),
+}
+
+impl WorkerSource {
+    fn start(&mut self) -> Result<IpcWorker, FandangoModuleInitError> {
+        match self {
+            Self::Spawn(spec) => IpcWorker::spawn(spec, false),
+            Self::Zygote { spec, zygote } => {
+                if let Some(z) = zygote.as_ref() {
+                    match Zygote::fork(z) {
+                        Ok(worker) => return Ok(worker),
+                        Err(e) => warn!(
+                            "fandango_ipc: zygote could not fork a worker ({}); starting a new zygote",
+                            e.format_report()
+                        ),
+                    }
+                }
+                let z = zygote.insert(Arc::new(Mutex::new(Zygote::spawn(spec)?)));
+                Zygote::fork(z)
+            }
+            Self::Connect(path) => IpcWorker::connect(path),
+        }
+    }
+}
+
+/// The process behind an [`IpcWorker`].
+enum WorkerProcess {
+    Spawned(Child),
+    /// Forked by a zygote, which is its parent and reaps it on our behalf.
+    Forked {
+        pid: u32,
+        zygote: Arc<Mutex<Zygote>>,
+        /// Exit status and peak RSS, once the zygote has reaped the worker.
+        reaped: Option<(ExitStatus, u64)>,
+    },
+}
+
+impl WorkerProcess {
+    fn id(&self) -> u32 {
+        match self {
+            Self::Spawned(child) => child.id(),
+            Self::Forked { pid, .. } => *pid,
+        }
+    }
+
+    fn kill(&mut self) -> io::Result<()> {
+        match self {
+            Self::Spawned(child) => child.kill(),
+            Self::Forked { .. } => self.signal_forked(libc::SIGKILL),
+        }
+    }
+
+    fn terminate(&mut self) -> io::Result<()> {
+        match self {
+            // SAFETY: plain kill(2) on a child that `Child` has not reaped yet.
+            Self::Spawned(child) => match unsafe { libc::kill(child.id() as _, libc::SIGTERM) } {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(()),
+            },
+            Self::Forked { .. } => self.signal_forked(libc::SIGTERM),
+        }
+    }
+
+    /// Signal a forked worker, unless it has exited already or its zygote is gone.
+    ///
+    /// Only the zygote may reap the worker while it is alive. Once the zygote has died, the worker
+    /// is reparented to init, which reaps it whenever it exits, so its pid may belong to an
+    /// unrelated process by now and no signal is sent at all.
+    fn signal_forked(&mut self, signal: libc::c_int) -> io::Result<()> {
+        let Self::Forked {
+            pid,
+            zygote,
+            reaped,
+        } = self
+        else {
+            unreachable!("signal_forked on a spawned worker");
+        };
+        if reaped.is_some() {
+            return Ok(());
+        }
+        let mut z = lock_zygote(zygote);
+        match z.wait(*pid, true) {
+            Ok(None) => {}
+            Ok(Some(status)) => {
+                *reaped = Some(status);
+                return Ok(());
+            }
+            Err(e) => {
+                warn!("fandango_ipc: not signalling worker pid={pid}, its zygote is gone ({e})");
+                return Ok(());
+            }
+        }
+        // SAFETY: plain kill(2). The zygote has just reported the worker as running, so the pid is
+        // still its unreaped child, and we hold the zygote's lock, so no `wait` reaps it meanwhile.
+        match unsafe { libc::kill(*pid as libc::pid_t, signal) } {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Poll for an exit until `deadline`; whether the process is gone.
+    fn wait_until(&mut self, deadline: Instant) -> bool {
+        loop {
+            match self.try_wait() {
+                Ok(Some(_)) => return true,
+                Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(5)),
+                Ok(None) => return false,
+                Err(e) => {
+                    warn!("fandango_ipc: try_wait on pid={} failed ({e})", self.id());
+                    return false;
+                }
+            }
+        }
+    }
+
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        match self {
+            Self::Spawned(child) => child.wait(),
+            Self::Forked { .. } => self.wait_with_peak_rss().map(|(status, _)| status),
+        }
+    }
+
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        match self {
+            Self::Spawned(child) => child.try_wait(),
+            Self::Forked {
+                reaped: Some((status, _)),
+                ..
+            } => Ok(Some(*status)),
+            Self::Forked {
+                pid,
+                zygote,
+                reaped,
+            } => {
+                *reaped = lock_zygote(zygote).wait(*pid, true)?;
+                Ok(reaped.map(|(status, _)| status))
+            }
+        }
+    }
+
+    /// Like [`Self::wait`], but also returns the peak RSS in bytes.
+    ///
+    /// Reaps a spawned child behind the back of its [`Child`], so only use it when the process is
+    /// not waited for again.
+    fn wait_with_peak_rss(&mut self) -> io::Result<(ExitStatus, u64)> {
+        let status = match self {
+            Self::Spawned(child) => {
+                reap(child.id(), false)?.map(|(status, rss)| (ExitStatus::from_raw(status), rss))
+            }
+            Self::Forked {
+                reaped: Some(status),
+                ..
+            } => Some(*status),
+            Self::Forked {
+                pid,
+                zygote,
+                reaped,
+            } => {
+                *reaped = lock_zygote(zygote).wait(*pid, false)?;
+                *reaped
+            }
+        };
+        status.ok_or_else(|| io::Error::other("no exit status for a blocking wait"))
+    }
+}
+
+/// A worker that has loaded the interface and called `setup`, and forks fresh workers from that
+/// state on request. Forking takes milliseconds instead of the seconds a new worker spends
+/// starting Python and parsing the spec.
+struct Zygote {
+    /// The zygote process; its IPC socket carries [`ZygoteReq`]s after the usual handshake, and
+    /// the sockets of the workers it forks. `None` once dropped.
+    control: Option<IpcWorker>,
+    /// Forward the output of forked workers to the log ([`FandangoWorkerOutput::Log`]).
+    log_output: bool,
+}
+
+impl Zygote {
+    fn spawn(spec: &WorkerSpec) -> Result<Self, FandangoModuleInitError> {
+        let control = IpcWorker::spawn(spec, true)?;
+        info!("fandango_ipc: started zygote ({})", control.describe());
+        Ok(Self {
+            control: Some(control),
+            log_output: spec.output == FandangoWorkerOutput::Log,
+        })
+    }
+
+    /// Send `req`, followed by `fds` if there are any, and read the reply.
+    fn request(&mut self, req: &ZygoteReq, fds: &[RawFd]) -> Result<ZygoteResp, String> {
+        let control = self.control.as_mut().ok_or("zygote shut down")?;
+        write_msg(&mut control.tx, req)?;
+        if !fds.is_empty() {
+            send_fds(&control.tx.get_ref().inner, fds).map_err(|e| e.to_string())?;
+        }
+        read_msg(&mut control.rx)?.ok_or_else(|| "zygote exited".to_string())
+    }
+
+    /// Have the zygote fork a new worker and connect to it.
+    fn fork(zygote: &Arc<Mutex<Zygote>>) -> Result<IpcWorker, FandangoModuleInitError> {
+        let failed = |e: String| {
+            subprocess_init(FandangoSubprocessInitIpc::HandshakeFailed(format!(
+                "zygote could not fork a worker: {e}"
+            )))
+        };
+        let io_err = |e| subprocess_init(FandangoSubprocessInitIpc::Io(e));
+        // Release the lock before the handshake: a failing handshake reaps the worker through it.
+        let (sock, pid, stderr_tail) = {
+            let mut z = lock_zygote(zygote);
+            let (sock, worker_sock) = UnixStream::pair().map_err(io_err)?;
+            let output = z.log_output;
+            let pipes = if output {
+                Some((io::pipe().map_err(io_err)?, io::pipe().map_err(io_err)?))
+            } else {
+                None
+            };
+            let mut fds = vec![worker_sock.as_raw_fd()];
+            if let Some(((_, stdout), (_, stderr))) = &pipes {
+                fds.extend([stdout.as_raw_fd(), stderr.as_raw_fd()]);
+            }
+            let resp = z.request(&ZygoteReq::Fork { output }, &fds);
+            // Only the worker may hold the other ends, so the readers see EOF when it exits.
+            drop(worker_sock);
+            let pipes = pipes.map(|((stdout, _), (stderr, _))| (stdout, stderr));
+            let pid = match resp.map_err(failed)? {
+                ZygoteResp::Forked(pid) => pid,
+                ZygoteResp::Failed(e) => return Err(failed(e)),
+                ZygoteResp::Status(_) => return Err(failed("unexpected response".to_string())),
+            };
+            let stderr_tail = match pipes {
+                Some((stdout, stderr)) => {
+                    let tail = StderrTail::default();
+                    forward_output_to_log(pid, stdout, stderr, tail.clone()).map_err(io_err)?;
+                    Some(tail)
+                }
+                None => None,
+            };
+            (sock, pid, stderr_tail)
+        };
+        IpcWorker::from_stream(
+            sock,
+            Some(WorkerProcess::Forked {
+                pid,
+                zygote: Arc::clone(zygote),
+                reaped: None,
+            }),
+            stderr_tail,
+        )
+    }
+
+    /// Reap one of the zygote's workers: its exit status and peak RSS in bytes, or `Ok(None)` if
+    /// `nohang` and it is still running.
+    fn wait(&mut self, pid: u32, nohang: bool) -> io::Result<Option<(ExitStatus, u64)>> {
+        match self
+            .request(&ZygoteReq::Wait { pid, nohang }, &[])
+            .map_err(io::Error::other)?
+        {
+            ZygoteResp::Status(status) => {
+                Ok(status.map(|(status, rss)| (ExitStatus::from_raw(status), rss)))
+            }
+            ZygoteResp::Failed(e) => Err(io::Error::other(e)),
+            ZygoteResp::Forked(_) => Err(io::Error::other("unexpected response from zygote")),
+        }
+    }
+}
+
+impl Drop for Zygote {
+    fn drop(&mut self) {
+        // Forked workers keep running until their own connections close.
+        if let Some(control) = self.control.take() {
+            control.retire(&FandangoShutdownPolicy::default(), false);
+        }
+    }
+}
+
+fn lock_zygote(zygote: &Mutex<Zygote>) -> MutexGuard<'_, Zygote> {
+    // A panic while holding the lock leaves at worst a desynchronized socket, which the next
+    // request reports as an error.
+    zygote.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A running worker and its IPC socket.
+struct IpcWorker {
+    /// `None` when connected to a server we did not spawn.
+    process: Option<WorkerProcess>,
+    /// Announced by the worker in its handshake.
+    capabilities: FandangoCapabilities,
+    /// Requests sent to this worker so far.
+    requests: u64,
+    /// When [`FandangoRecyclePolicy::max_rss`] last read the RSS (initially the start).
+    rss_checked: Instant,
+    /// Requests without a reply yet, oldest first; only tracked with a forensics directory.
+    unanswered: BTreeMap<u64, IpcReq>,
+    /// Set with [`FandangoWorkerOutput::Log`].
+    stderr_tail: Option<StderrTail>,
+    tx: io::BufWriter<DeadlineWriter<UnixStream>>,
+    rx: io::BufReader<DeadlineReader<UnixStream>>,
+}
+
+impl IpcWorker {
+    /// Spawn a worker, or a zygote.
+    fn spawn(spec: &WorkerSpec, zygote: bool) -> Result<Self, FandangoModuleInitError> {
+        let exe = match &spec.exe {
+            Some(exe) => exe.clone(),
+            None => std::env::current_exe()
+                .map_err(|e| subprocess_init(FandangoSubprocessInitIpc::Io(e)))?,
+        };
+
+        let (sock, child_sock) =
+            UnixStream::pair().map_err(|e| subprocess_init(FandangoSubprocessInitIpc::Io(e)))?;
+        let child_fd = child_sock.as_raw_fd();
+
+        let mut cmd = Command::new(&exe);
+        cmd.arg(if zygote {
+            IPC_ZYGOTE_ARG
+        } else {
+            IPC_WORKER_ARG
+        })
+        .arg(&spec.python_interface_path)
+        .arg(&spec.fandango_file)
+        .arg(&spec.kwargs_json)
+        .stdin(Stdio::null());
+        spec.output.apply(&mut cmd);
+        if let Some(seed) = spec.seed {
+            cmd.env(IPC_SEED_ENV, seed.to_string());
+        }
+        if !spec.python_path.is_empty() {
+            let inherited = std::env::var_os("PYTHONPATH");
+            let paths = spec
+                .python_path
+                .iter()
+                .cloned()
+                .chain(inherited.iter().flat_map(std::env::split_paths));
+            let joined = std::env::join_paths(paths).map_err(|e| {
+                subprocess_init(FandangoSubprocessInitIpc::Io(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    e,
+                )))
+            })?;
+            cmd.env("PYTHONPATH", joined);
+        }
+        // SAFETY: only async-signal-safe libc calls, no allocation.
+        unsafe {
+            cmd.pre_exec(move || {
+                if child_fd == IPC_WORKER_FD {
+                    // dup2 onto itself keeps FD_CLOEXEC set, so clear it by hand.
+                    return clear_cloexec(child_fd);
+                }
+                if libc::dup2(child_fd, IPC_WORKER_FD) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        spec.limits.apply(&mut cmd);
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| subprocess_init(FandangoSubprocessInitIpc::Io(e)))?;
+        drop(child_sock);
+
+        let pid = child.id();
+        let mut stderr_tail = None;
+        if spec.output == FandangoWorkerOutput::Log {
+            let stdout = child
+                .stdout
+                .take()
+                .ok_or_else(|| subprocess_init(FandangoSubprocessInitIpc::MissingPipe("stdout")))?;
+            let stderr = child
+                .stderr
+                .take()
+                .ok_or_else(|| subprocess_init(FandangoSubprocessInitIpc::MissingPipe("stderr")))?;
+            let tail = StderrTail::default();
+            forward_output_to_log(pid, stdout, stderr, tail.clone())
+                .map_err(|e| subprocess_init(FandangoSubprocessInitIpc::Io(e)))?;
+            stderr_tail = Some(tail);
+        }
+
+        Self::from_stream(sock, Some(WorkerProcess::Spawned(child)), stderr_tail)
+    }
+
+    fn connect(path: &Path) -> Result<Self, FandangoModuleInitError> {
+        let sock = UnixStream::connect(path)
+            .map_err(|e| subprocess_init(FandangoSubprocessInitIpc::Io(e)))?;
+        Self::from_stream(sock, None, None)
+    }
+
+    /// Wrap the IPC socket of a started worker and wait for its handshake.
+    fn from_stream(
+        sock: UnixStream,
+        process: Option<WorkerProcess>,
+        stderr_tail: Option<StderrTail>,
+    ) -> Result<Self, FandangoModuleInitError> {
+        let tx = io::BufWriter::new(DeadlineWriter::new(
+            sock.try_clone()
+                .map_err(|e| subprocess_init(FandangoSubprocessInitIpc::Io(e)))?,
+        ));
+        let mut worker = Self {
+            process,
+            capabilities: FandangoCapabilities::default(),
+            requests: 0,
+            rss_checked: Instant::now(),
+            unanswered: BTreeMap::new(),
+            stderr_tail,
+            tx,
+            rx: io::BufReader::new(DeadlineReader::new(sock)),
+        };
+        worker.handshake()?;
+        Ok(worker)
+    }
+
+    fn handshake(&mut self) -> Result<(), FandangoModuleInitError> {
+        match read_msg::<_, u32>(&mut self.rx) {
+            Ok(Some(IPC_PROTOCOL_VERSION)) => {}
+            Ok(Some(worker)) => {
+                ipc_fail(self.process.as_mut(), "IPC protocol version mismatch", None);
+                return Err(subprocess_init(
+                    FandangoSubprocessInitIpc::VersionMismatch {
+                        worker,
+                        ours: IPC_PROTOCOL_VERSION,
+                    },
+                ));
+            }
+            Ok(None) => {
+                return Err(subprocess_init(FandangoSubprocessInitIpc::HandshakeFailed(
+                    ipc_fail(self.process.as_mut(), "EOF during handshake", None).to_string(),
+                )));
+            }
+            Err(e) => {
+                return Err(subprocess_init(FandangoSubprocessInitIpc::HandshakeFailed(
+                    ipc_fail(self.process.as_mut(), e, None).to_string(),
+                )));
+            }
+        }
+        let hs: IpcHandshake = match read_msg(&mut self.rx) {
+            Ok(Some(h)) => h,
+            Ok(None) => {
+                return Err(subprocess_init(FandangoSubprocessInitIpc::HandshakeFailed(
+                    ipc_fail(self.process.as_mut(), "EOF during handshake", None).to_string(),
+                )));
+            }
+            Err(e) => {
+                return Err(subprocess_init(FandangoSubprocessInitIpc::HandshakeFailed(
+                    ipc_fail(self.process.as_mut(), e, None).to_string(),
+                )));
+            }
+        };
+        match hs {
+            IpcHandshake::Ready(capabilities) => {
+                self.capabilities = capabilities;
+                Ok(())
+            }
+            IpcHandshake::Failed(msg) => {
+                error!(
+                    "fandango_ipc: worker setup failed ({}): {msg}",
+                    self.describe()
+                );
+                if let Some(process) = self.process.as_mut() {
+                    let _ = process.wait();
+                }
+                Err(subprocess_init(
+                    FandangoSubprocessInitIpc::WorkerSetupFailed(msg),
+                ))
+            }
+        }
+    }
+
+    /// Stop the worker as described by `policy`. Without `teardown`, no [`IpcReq::Shutdown`] is
+    /// sent and closing the socket is the only request to exit (e.g. for a zygote).
+    fn retire(self, policy: &FandangoShutdownPolicy, teardown: bool) {
+        let Self {
+            process,
+            mut tx,
+            mut rx,
+            ..
+        } = self;
+        let Some(mut process) = process else {
+            return;
+        };
+        let pid = process.id();
+        let deadline = Instant::now() + policy.grace;
+        if teardown {
+            request_shutdown(&mut tx, &mut rx, Some(deadline), pid);
+        }
+        drop(tx);
+        drop(rx);
+        if process.wait_until(deadline) {
+            return;
+        }
+        warn!(
+            "fandango_ipc: worker pid={pid} did not exit within {:?}; sending SIGTERM",
+            policy.grace
+        );
+        let _ = process.terminate();
+        if process.wait_until(Instant::now() + policy.term_wait) {
+            return;
+        }
+        warn!(
+            "fandango_ipc: worker pid={pid} did not exit within {:?} of SIGTERM; sending SIGKILL",
+            policy.term_wait
+        );
+        let _ = process.kill();
+        let _ = process.wait();
+    }
+
+    /// `pid=N` for spawned workers, for log messages.
+    fn describe(&self) -> String {
+        match &self.process {
+            Some(process) => format!("pid={}", process.id()),
+            None => "socket server".to_string(),
+        }
+    }
+
+    /// The RSS of process `pid` in bytes, unless it was read less than [`RSS_CHECK_INTERVAL`] ago.
+    fn sample_rss(&mut self, pid: u32) -> Option<u64> {
+        if self.rss_checked.elapsed() < RSS_CHECK_INTERVAL {
+            return None;
+        }
+        self.rss_checked = Instant::now();
+        proc_status_bytes(pid, "VmRSS")
+    }
+}
+
+/// Builder for a [`FandangoSubprocessModule`], created by [`FandangoSubprocessModule::builder`].
+///
+/// The resource limits are applied to the worker before it starts Python (and again on every
+/// restart), so a runaway spec fails inside the worker instead of taking the host down with it.
+#[derive(Clone)]
+pub struct FandangoSubprocessBuilder {
+    exe: Option<PathBuf>,
+    python_interface_path: Option<String>,
+    fandango_file: String,
+    kwargs: Vec<(String, String)>,
+    limits: WorkerLimits,
+    output: FandangoWorkerOutput,
+    call_timeout: Option<Duration>,
+    restart_policy: Option<FandangoRestartPolicy>,
+    recycle_policy: Option<FandangoRecyclePolicy>,
+    forensics_dir: Option<PathBuf>,
+    shutdown_policy: FandangoShutdownPolicy,
+    pipeline_depth: usize,
+    zygote: bool,
+    seed: Option<u64>,
+    python_path: Vec<PathBuf>,
+}
+
+impl FandangoSubprocessBuilder {
+    /// Use a custom Python interface instead of `examples/run_fandango.py`.
+    pub fn python_interface(mut self, path: &str) -> Self {
+        self.python_interface_path = Some(path.to_string());
+        self
+    }
+
+    /// Run `exe` as the worker instead of re-executing the current binary, e.g. the `fandango_worker`
+    /// binary of this crate. Useful for binaries built without the `ipc-worker-ctor` feature or
+    /// that cannot be re-executed.
+    pub fn worker_executable(mut self, exe: impl Into<PathBuf>) -> Self {
+        self.exe = Some(exe.into());
+        self
+    }
+
+    /// Cap the worker's virtual address space (`RLIMIT_AS`) at `bytes`.
+    ///
+    /// Allocations beyond that fail inside Python (usually as `MemoryError`, which the call returns
+    /// as [`FandangoCallError::PythonException`]) instead of waking the OOM killer. Leave some
+    /// headroom: the interpreter and its native extensions reserve more address space than they use.
+    pub fn memory_limit(mut self, bytes: u64) -> Self {
+        self.limits.address_space = Some(bytes);
+        self
+    }
+
+    /// Cap the worker's total CPU time (`RLIMIT_CPU`, rounded up to whole seconds).
+    ///
+    /// The limit covers the lifetime of a worker, not a single call; the kernel kills the worker
+    /// when it is exceeded. Combine it with a restart policy for long campaigns, and use
+    /// [`Self::call_timeout`] to bound individual calls.
+    pub fn cpu_time_limit(mut self, limit: Duration) -> Self {
+        self.limits.cpu_time = Some(limit);
+        self
+    }
+
+    /// Set the worker's nice level (`setpriority`), e.g. `10` so target executions get the CPU first.
+    /// Values below the fuzzer's own level usually require privileges.
+    pub fn nice(mut self, nice: i32) -> Self {
+        self.limits.nice = Some(nice);
+        self
+    }
+
+    /// Run the worker in its own process group, so e.g. Ctrl-C in the terminal only reaches the
+    /// fuzzer, which then shuts the worker down itself.
+    pub fn new_process_group(mut self, enabled: bool) -> Self {
+        self.limits.new_process_group = enabled;
+        self
+    }
+
+    /// Where the worker's stdout/stderr go; see [`FandangoWorkerOutput`].
+    pub fn worker_output(mut self, output: FandangoWorkerOutput) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// See [`FandangoSubprocessModule::set_call_timeout`].
+    pub fn call_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.call_timeout = timeout;
+        self
+    }
+
+    /// See [`FandangoSubprocessModule::set_restart_policy`].
+    pub fn restart_policy(mut self, policy: Option<FandangoRestartPolicy>) -> Self {
+        self.restart_policy = policy;
+        self
+    }
+
+    /// See [`FandangoSubprocessModule::set_recycle_policy`].
+    pub fn recycle_policy(mut self, policy: Option<FandangoRecyclePolicy>) -> Self {
+        self.recycle_policy = policy;
+        self
+    }
+
+    /// See [`FandangoSubprocessModule::set_forensics_dir`].
+    pub fn forensics_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.forensics_dir = dir;
+        self
+    }
+
+    /// See [`FandangoSubprocessModule::set_shutdown_policy`].
+    pub fn shutdown_policy(mut self, policy: FandangoShutdownPolicy) -> Self {
+        self.shutdown_policy = policy;
+        self
+    }
+
+    /// See [`FandangoSubprocessModule::set_pipeline_depth`].
+    pub fn pipeline_depth(mut self, depth: usize) -> Self {
+        self.pipeline_depth = depth;
+        self
+    }
+
+    /// Seed Python's `random` module in the worker before `setup` runs, so a campaign can be
+    /// replayed. Restarted workers start from the same seed again; workers forked by a
+    /// [zygote](Self::zygote) are reseeded by Python on fork, so only the zygote's own state is
+    /// fixed then.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Prepend `dir` to the worker's `PYTHONPATH`, e.g. a virtualenv's `site-packages` or a
+    /// Fandango checkout. Can be called several times; earlier directories come first.
+    pub fn python_path(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.python_path.push(dir.into());
+        self
+    }
+
+    /// Start workers by forking a zygote: a worker process that has loaded the interface and called
+    /// `setup` once, and then only forks. Restarts and recycling then take milliseconds instead of
+    /// re-executing the binary and parsing the spec again.
+    ///
+    /// Every forked worker starts from the state right after `setup`; Python's at-fork hooks run,
+    /// so `random` is reseeded, but other generator state is copied as is. The interface must not
+    /// start threads in `setup`, since only the forking thread survives a `fork`. Resource limits
+    /// apply to the zygote and are inherited by its workers; a zygote that died is started again
+    /// on the next restart.
+    pub fn zygote(mut self, enabled: bool) -> Self {
+        self.zygote = enabled;
+        self
+    }
+
+    /// Spawn the worker and wait for its handshake.
+    pub fn build(self) -> Result<FandangoSubprocessModule, FandangoModuleInitError> {
+        let python_interface_path = match self.python_interface_path {
+            Some(path) => path,
+            None => PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("examples/run_fandango.py")
+                .to_str()
+                .ok_or_else(|| {
+                    FandangoModuleInitError::FilePathError(
+                        "default interface path is not UTF-8".to_string(),
+                    )
+                })?
+                .to_string(),
+        };
+        let kwargs: Vec<(&str, &str)> = self
+            .kwargs
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        let spec = WorkerSpec {
+            exe: self.exe,
+            python_interface_path,
+            fandango_file: self.fandango_file,
+            kwargs_json: kwargs_json(&kwargs)
+                .map_err(|e| subprocess_init(FandangoSubprocessInitIpc::KwargsJson(e)))?,
+            limits: self.limits,
+            output: self.output,
+            seed: self.seed,
+            python_path: self.python_path,
+        };
+        let mut source = if self.zygote {
+            WorkerSource::Zygote { spec, zygote: None }
+        } else {
+            WorkerSource::Spawn(spec)
+        };
+        let worker = source.start()?;
+        Ok(FandangoSubprocessModule {
+            source,
+            capabilities: worker.capabilities,
+            worker: Some(worker),
+            call_timeout: self.call_timeout,
+            restart_policy: self.restart_policy,
+            restarts: 0,
+            consecutive_restarts: 0,
+            shut_down: false,
+            next_id: 0,
+            pipeline_depth: self.pipeline_depth,
+            in_flight: VecDeque::new(),
+            stashed: HashMap::new(),
+            recycle_policy: self.recycle_policy,
+            recycles: 0,
+            forensics_dir: self.forensics_dir,
+            shutdown_policy: self.shutdown_policy,
+        })
+    }
+}
+
+/// A module for running Fandango in a subprocess.
+///
+/// Trades off some speed (due to IPC) for more robust error handling (e.g. if the child process OOMs).
+///
+/// Essentially a wrapper around `FandangoInprocessModule` that runs it in a subprocess and communicates via IPC.
+pub struct FandangoSubprocessModule {
+    source: WorkerSource,
+    worker: Option<IpcWorker>,
+    call_timeout: Option<Duration>,
+    restart_policy: Option<FandangoRestartPolicy>,
+    restarts: u32,
+    /// Restarts since the last successful call; drives the backoff.
+    consecutive_restarts: u32,
+    /// Set by [`FandangoSubprocessModule::shutdown`]; a shut down module is never restarted.
+    shut_down: bool,
+    /// `id` of the next request frame.
+    next_id: u64,
+    /// Number of `Next` requests to keep in flight; 0 disables pipelining.
+    pipeline_depth: usize,
+    /// Ids of pipelined `Next` requests, oldest first.
+    in_flight: VecDeque<u64>,
+    /// Replies that arrived while waiting for a different request.
+    stashed: HashMap<u64, IpcResp>,
+    capabilities: FandangoCapabilities,
+    recycle_policy: Option<FandangoRecyclePolicy>,
+    /// Where crash reports of dead workers go; `None` disables them.
+    forensics_dir: Option<PathBuf>,
+    shutdown_policy: FandangoShutdownPolicy,
+    recycles: u32,
+}
+
+impl FandangoSubprocessModule {
+    pub fn new(
+        fandango_file: &str,
+        kwargs: &[(&str, &str)],
+    ) -> Result<Self, FandangoModuleInitError> {
+        Self::builder(fandango_file, kwargs).build()
+    }
+
+    pub fn with_custom_python_interface(
+        python_interface_path: &str,
+        fandango_file: &str,
+        kwargs: &[(&str, &str)],
+    ) -> Result<Self, FandangoModuleInitError> {
+        Self::builder(fandango_file, kwargs)
+            .python_interface(python_interface_path)
+            .build()
+    }
+
+    /// Start configuring a worker, e.g. to apply resource limits. See [`FandangoSubprocessBuilder`].
+    pub fn builder(fandango_file: &str, kwargs: &[(&str, &str)]) -> FandangoSubprocessBuilder {
+        FandangoSubprocessBuilder {
+            exe: None,
+            python_interface_path: None,
+            fandango_file: fandango_file.to_string(),
+            kwargs: kwargs
+                .iter()
+                .map(|&(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            limits: WorkerLimits::default(),
+            output: FandangoWorkerOutput::default(),
+            call_timeout: None,
+            restart_policy: None,
+            recycle_policy: None,
+            forensics_dir: None,
+            shutdown_policy: FandangoShutdownPolicy::default(),
+            pipeline_depth: 0,
+            zygote: false,
+            seed: None,
+            python_path: Vec::new(),
+        }
+    }
+
+    /// Connect to a server started with [`serve_unix_socket`] (e.g. the `fandango_worker` binary)
+    /// instead of spawning a worker.
+    ///
+    /// All clients of one server share its Fandango instance. Timeouts and restarts work as with a
+    /// spawned worker, except that a timed out call only drops the connection (the server is not
+    /// ours to kill) and a restart reconnects.
+    pub fn connect(socket_path: impl Into<PathBuf>) -> Result<Self, FandangoModuleInitError> {
+        let mut source = WorkerSource::Connect(socket_path.into());
+        let worker = source.start()?;
+        Ok(Self {
+            source,
+            capabilities: worker.capabilities,
+            worker: Some(worker),
+            call_timeout: None,
+            restart_policy: None,
+            restarts: 0,
+            consecutive_restarts: 0,
+            shut_down: false,
+            next_id: 0,
+            pipeline_depth: 0,
+            in_flight: VecDeque::new(),
+            stashed: HashMap::new(),
+            recycle_policy: None,
+            recycles: 0,
+            forensics_dir: None,
+            shutdown_policy: FandangoShutdownPolicy::default(),
+        })
+    }
+
+    /// Set a deadline for every [`FandangoClient`] call (builder style). See [`Self::set_call_timeout`].
+    pub fn with_call_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.set_call_timeout(timeout);
+        self
+    }
+
+    /// Set a deadline for every [`FandangoClient`] call; `None` (the default) waits forever.
+    ///
+    /// If the worker does not answer in time (e.g. Fandango's search hangs on an unsatisfiable
+    /// constraint), it is killed and the call returns [`FandangoCallError::Timeout`].
+    pub fn set_call_timeout(&mut self, timeout: Option<Duration>) {
+        self.call_timeout = timeout;
+    }
+
+    pub fn call_timeout(&self) -> Option<Duration> {
+        self.call_timeout
+    }
+
+    /// Restart the worker after it dies (builder style). See [`Self::set_restart_policy`].
+    pub fn with_restart_policy(mut self, policy: Option<FandangoRestartPolicy>) -> Self {
+        self.set_restart_policy(policy);
+        self
+    }
+
+    /// Restart the worker according to `policy` after it dies or times out; `None` (the default)
+    /// leaves the module without a worker, so every later call fails.
+    pub fn set_restart_policy(&mut self, policy: Option<FandangoRestartPolicy>) {
+        self.restart_policy = policy;
+    }
+
+    /// Keep `depth` `next_input` requests in flight (builder style). See [`Self::set_pipeline_depth`].
+    pub fn with_pipeline_depth(mut self, depth: usize) -> Self {
+        self.set_pipeline_depth(depth);
+        self
+    }
+
+    /// Keep `depth` `next_input` requests queued at the worker, so it generates the next inputs
+    /// while the fuzzer executes the current one; 0 (the default) sends one request per call.
+    ///
+    /// Other calls can be made at any time; their replies are matched by request id. A spawned
+    /// worker answers in order, so such calls wait for the queued `Next` requests first. Inputs that
+    /// are still in flight when the worker dies are lost.
+    pub fn set_pipeline_depth(&mut self, depth: usize) {
+        self.pipeline_depth = depth;
+    }
+
+    /// Which optional functions the worker's Python interface exposes, as announced in its
+    /// handshake. Kept from the last worker while none is running.
+    pub fn capabilities(&self) -> FandangoCapabilities {
+        self.capabilities
+    }
+
+    /// Replace the worker proactively (builder style). See [`Self::set_recycle_policy`].
+    pub fn with_recycle_policy(mut self, policy: Option<FandangoRecyclePolicy>) -> Self {
+        self.set_recycle_policy(policy);
+        self
+    }
+
+    /// Replace the worker with a fresh one according to `policy`; `None` (the default) keeps it for
+    /// as long as it lives.
+    pub fn set_recycle_policy(&mut self, policy: Option<FandangoRecyclePolicy>) {
+        self.recycle_policy = policy;
+    }
+
+    /// Write crash reports of dead workers to `dir` (builder style). See [`Self::set_forensics_dir`].
+    pub fn with_forensics_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.set_forensics_dir(dir);
+        self
+    }
+
+    /// Write a crash report into a new subdirectory of `dir` whenever a worker dies; `None` (the
+    /// default) only logs the exit status.
+    ///
+    /// A report holds the request the worker died on (with the input bytes of a `parse_input` call,
+    /// so it can be reproduced), the exit code or signal, the peak RSS and, with
+    /// [`FandangoWorkerOutput::Log`], the last lines of its stderr. The directory is returned as
+    /// `forensics` in [`FandangoCallError::WorkerDied`]. Workers killed after a timeout get no
+    /// report.
+    pub fn set_forensics_dir(&mut self, dir: Option<PathBuf>) {
+        self.forensics_dir = dir;
+    }
+
+    /// Stop workers according to `policy` (builder style). See [`Self::set_shutdown_policy`].
+    pub fn with_shutdown_policy(mut self, policy: FandangoShutdownPolicy) -> Self {
+        self.set_shutdown_policy(policy);
+        self
+    }
+
+    /// How a worker is stopped when the module is dropped or the worker is recycled; see
+    /// [`FandangoShutdownPolicy`].
+    pub fn set_shutdown_policy(&mut self, policy: FandangoShutdownPolicy) {
+        self.shutdown_policy = policy;
+    }
+
+    /// Number of times a worker has been recycled so far (not counting restarts after crashes).
+    pub fn recycle_count(&self) -> u32 {
+        self.recycles
+    }
+
+    /// Number of times the worker has been restarted so far.
+    pub fn restart_count(&self) -> u32 {
+        self.restarts
+    }
+
+    /// Let the worker run the interface's `teardown`, close the IPC connection and wait for the
+    /// worker to exit.
+    ///
+    /// Prefer this over relying on [`Drop`] when you want a clean shutdown without the bounded
+    /// waits of the [`FandangoShutdownPolicy`]. When connected to a server, this only closes the
+    /// connection and returns a successful status.
+    pub fn shutdown(&mut self) -> io::Result<ExitStatus> {
+        self.shut_down = true;
+        let Some(IpcWorker {
+            mut process,
+            mut tx,
+            mut rx,
+            ..
+        }) = self.worker.take()
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "Fandango IPC subprocess already shut down",
+            ));
+        };
+        if let Some(process) = process.as_ref() {
+            request_shutdown(&mut tx, &mut rx, None, process.id());
+        }
+        drop(tx);
+        drop(rx);
+        match process.as_mut() {
+            Some(process) => process.wait(),
+            None => Ok(ExitStatus::default()),
+        }
+    }
+
+    /// Ask the worker how it is doing, without calling into the interface.
+    ///
+    /// Goes through the same path as every other call: it is subject to the call timeout, and a
+    /// worker that died is restarted first if the restart policy allows it. With pipelining, the
+    /// reply waits for the queued `next_input` requests. When connected to a multiplexed server, the
+    /// report is from whichever pool worker took the request.
+    pub fn health(&mut self) -> Result<FandangoWorkerHealth, FandangoCallError> {
+        match self.rpc(&IpcReq::Health)? {
+            IpcResp::HealthOk(health) => Ok(health),
+            IpcResp::CallErr(e) => Err(e),
+            _ => Err(FandangoCallError::Protocol(
+                "unexpected response to Health from worker".into(),
+            )),
+        }
+    }
+
+    /// Round trip to the worker (see [`Self::health`]); returns how long it took.
+    pub fn ping(&mut self) -> Result<Duration, FandangoCallError> {
+        let start = Instant::now();
+        self.health()?;
+        Ok(start.elapsed())
+    }
+
+    /// Whether the worker is gone for good: it died or was shut down, and the restart policy will
+    /// not start another one, so every further call fails.
+    pub(crate) fn is_beyond_restart(&self) -> bool {
+        self.worker.is_none()
+            && (self.shut_down
+                || self
+                    .restart_policy
+                    .is_none_or(|p| self.restarts >= p.max_restarts))
+    }
+
+    /// Start a new worker if the restart policy allows it.
+    fn respawn(&mut self) -> Result<(), FandangoCallError> {
+        let gone = |reason: String| FandangoCallError::WorkerDied {
+            reason,
+            exit_code: None,
+            signal: None,
+            forensics: None,
+        };
+        let policy = match self.restart_policy {
+            Some(p) if !self.shut_down => p,
+            _ => return Err(gone("IPC subprocess shut down".to_string())),
+        };
+        if self.restarts >= policy.max_restarts {
+            return Err(gone(format!(
+                "IPC worker restart limit ({}) reached",
+                policy.max_restarts
+            )));
+        }
+        thread::sleep(policy.backoff(self.consecutive_restarts));
+        self.restarts += 1;
+        self.consecutive_restarts += 1;
+        let worker = self
+            .source
+            .start()
+            .map_err(|e| gone(format!("IPC worker restart failed: {}", e.format_report())))?;
+        warn!(
+            "fandango_ipc: restarted worker ({}, restart {}/{})",
+            worker.describe(),
+            self.restarts,
+            policy.max_restarts
+        );
+        self.capabilities = worker.capabilities;
+        self.worker = Some(worker);
+        Ok(())
+    }
+
+    pub(crate) fn rpc(&mut self, req: &IpcReq) -> Result<IpcResp, FandangoCallError> {
+        let id = self.send(req)?;
+        self.wait_for(id)
+    }
+
+    /// Write a request frame without waiting for the reply; returns its `id` for [`Self::wait_for`].
+    fn send(&mut self, req: &IpcReq) -> Result<u64, FandangoCallError> {
+        self.recycle_if_due();
+        if self.worker.is_none() {
+            self.respawn()?;
+        }
+        let worker = self.worker.as_mut().expect("worker was just spawned");
+        worker.requests += 1;
+        let id = self.next_id;
+        self.next_id += 1;
+        if self.forensics_dir.is_some() {
+            worker.unanswered.insert(id, req.clone());
+        }
+        let writer = worker.tx.get_mut();
+        writer.deadline = self.call_timeout.map(|t| Instant::now() + t);
+        writer.expired = false;
+        match write_msg(&mut worker.tx, &IpcFrame { id, body: req }) {
+            Ok(()) => Ok(id),
+            Err(e) => Err(self.fail(e)),
+        }
+    }
+
+    /// Replace a spawned worker that the recycle policy considers worn out.
+    ///
+    /// Never fails the current call: errors of the drained requests stay with those requests, and
+    /// if no replacement can be started, the old worker keeps serving.
+    fn recycle_if_due(&mut self) {
+        let (Some(policy), Some(worker)) = (self.recycle_policy, self.worker.as_mut()) else {
+            return;
+        };
+        let Some(pid) = worker.process.as_ref().map(WorkerProcess::id) else {
+            return;
+        };
+        let reason = if policy
+            .max_requests
+            .is_some_and(|max| worker.requests >= max)
+        {
+            format!("served {} requests", worker.requests)
+        } else if let Some(max) = policy.max_rss
+            && let Some(rss) = worker.sample_rss(pid)
+            && rss > max
+        {
+            format!("RSS is {} MiB", rss >> 20)
+        } else {
+            return;
+        };
+
+        // Collect the replies still owed by the old worker; `wait_for` finds them in `stashed`.
+        let mut drained = Vec::new();
+        for id in self.in_flight.clone() {
+            match self.wait_for(id) {
+                Ok(resp) => drained.push((id, resp)),
+                Err(e) => {
+                    // The worker died (or timed out) on this request and `fail` dropped it along
+                    // with the rest of the pipeline. The request still gets its error, and the
+                    // replacement below serves the current call.
+                    drained.push((id, IpcResp::CallErr(e)));
+                    break;
+                }
+            }
+        }
+        self.in_flight = drained.iter().map(|&(id, _)| id).collect();
+        self.stashed.extend(drained);
+
+        info!("fandango_ipc: recycling worker pid={pid} ({reason})");
+        match self.source.start() {
+            Ok(worker) => {
+                self.capabilities = worker.capabilities;
+                if let Some(old) = self.worker.replace(worker) {
+                    old.retire(&self.shutdown_policy, true);
+                }
+                self.recycles += 1;
+            }
+            // Without a worker, `send` goes on to the restart policy.
+            Err(e) => match self.worker.as_mut() {
+                Some(old) => {
+                    warn!(
+                        "fandango_ipc: could not start a replacement for worker pid={pid}, keeping it until the next threshold: {}",
+                        e.format_report()
+                    );
+                    old.requests = 0;
+                    old.rss_checked = Instant::now();
+                }
+                None => warn!(
+                    "fandango_ipc: could not start a replacement for worker pid={pid}: {}",
+                    e.format_report()
+                ),
+            },
+        }
+    }
+
+    /// Read frames until the reply to `id` arrives. Replies to other requests in flight may arrive
+    /// first (e.g. from a multiplexed server); they are kept for their own `wait_for`.
+    fn wait_for(&mut self, id: u64) -> Result<IpcResp, FandangoCallError> {
+        if let Some(resp) = self.stashed.remove(&id) {
+            return Ok(resp);
+        }
+        let Some(worker) = self.worker.as_mut() else {
+            return Err(FandangoCallError::WorkerDied {
+                reason: "IPC subprocess shut down".to_string(),
+                exit_code: None,
+                signal: None,
+                forensics: None,
+            });
+        };
+        let reader = worker.rx.get_mut();
+        reader.deadline = self.call_timeout.map(|t| Instant::now() + t);
+        reader.expired = false;
+        let reason = loop {
+            match read_msg::<_, IpcFrame<IpcResp>>(&mut worker.rx) {
+                Ok(Some(r)) if r.id == id => {
+                    worker.unanswered.remove(&r.id);
+                    self.consecutive_restarts = 0;
+                    return Ok(r.body);
+                }
+                Ok(Some(r)) if r.id < self.next_id => {
+                    worker.unanswered.remove(&r.id);
+                    self.stashed.insert(r.id, r.body);
+                }
+                Ok(Some(r)) => break format!("reply for unknown request {}", r.id),
+                Ok(None) => break "EOF from worker before response".to_string(),
+                Err(e) => break e,
+            }
+        };
+        Err(self.fail(reason))
+    }
+
+    /// Drop the worker after a broken frame and build the error for the current call.
+    ///
+    /// The stream is unusable afterwards; the worker is possibly restarted on the next call.
+    fn fail(&mut self, reason: String) -> FandangoCallError {
+        let Some(IpcWorker {
+            mut process,
+            tx,
+            rx,
+            unanswered,
+            stderr_tail,
+            ..
+        }) = self.worker.take()
+        else {
+            return FandangoCallError::Protocol(reason);
+        };
+        self.in_flight.clear();
+        self.stashed.clear();
+        let write_expired = tx.get_ref().expired;
+        if !write_expired && !rx.get_ref().expired {
+            // A worker answers in order, so the oldest unanswered request is the one it died on.
+            let crash = self.forensics_dir.as_deref().map(|dir| CrashContext {
+                dir,
+                request: unanswered.values().next(),
+                stderr_tail: stderr_tail.as_ref(),
+            });
+            return ipc_fail(process.as_mut(), reason, crash);
+        }
+        let timeout = self.call_timeout.unwrap_or_default();
+        let missed = if write_expired {
+            "read the request"
+        } else {
+            "answer"
+        };
+        match process.as_mut() {
+            Some(child) => {
+                warn!(
+                    "fandango_ipc: worker pid={} did not {missed} within {timeout:?}; killing it",
+                    child.id()
+                );
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            None => warn!(
+                "fandango_ipc: server did not {missed} within {timeout:?}; dropping the connection"
+            ),
+        }
+        FandangoCallError::Timeout(timeout)
+    }
+
+    /// Keep `pipeline_depth` `Next` requests in flight.
+    fn fill_pipeline(&mut self) -> Result<(), FandangoCallError> {
+        while self.in_flight.len() < self.pipeline_depth {
+            let id = self.send(&IpcReq::Next)?;
+            self.in_flight.push_back(id);
+        }
+        Ok(())
+    }
+
+    fn pipelined_next(&mut self) -> Result<IpcResp, FandangoCallError> {
+        self.fill_pipeline()?;
+        let id = self
+            .in_flight
+            .pop_front()
+            .expect("pipeline was just filled");
+        let resp = self.wait_for(id)?;
+        // Best effort: if the worker died meanwhile, the next call reports (and restarts) it.
+        let _ = self.fill_pipeline();
+        Ok(resp)
+    }
+}
+
+/// Send [`IpcReq::Shutdown`] and wait (until `deadline`, if any) for the reply; replies to requests
+/// still in flight are skipped. A failing `teardown` is only logged.
+fn request_shutdown(
+    tx: &mut io::BufWriter<DeadlineWriter<UnixStream>>,
+    rx: &mut io::BufReader<DeadlineReader<UnixStream>>,
+    deadline: Option<Instant>,
+    pid: u32,
+) {
+    let frame = IpcFrame {
+        id: SHUTDOWN_ID,
+        body: IpcReq::Shutdown,
+    };
+    tx.get_mut().deadline = deadline;
+    if write_msg(tx, &frame).is_err() {
+        return;
+    }
+    rx.get_mut().deadline = deadline;
+    loop {
+        match read_msg::<_, IpcFrame<IpcResp>>(rx) {
+            Ok(Some(IpcFrame {
+                id: SHUTDOWN_ID,
+                body,
+            })) => {
+                if let IpcResp::CallErr(e) = body {
+                    warn!("fandango_ipc: teardown in worker pid={pid} failed: {e}");
+                }
+                return;
+            }
+            Ok(Some(_)) => {}
+            Ok(None) | Err(_) => return,
+        }
+    }
+}
+
+/// Reap the worker after a broken frame. It is killed first in case it is still running (e.g. after
+/// a decoding error), so this never blocks on a live child. Without a child (socket server), only
+/// the error is built. With `crash`, a crash report is written as well.
+fn ipc_fail(
+    child: Option<&mut WorkerProcess>,
+    reason: impl std::fmt::Display,
+    crash: Option<CrashContext<'_>>,
+) -> FandangoCallError {
+    let pid = child.as_ref().map(|child| child.id());
+    let (status, peak_rss) = child
+        .and_then(|child| {
+            let _ = child.kill();
+            child.wait_with_peak_rss().ok()
+        })
+        .unzip();
+    let reason = reason.to_string();
+    let forensics = crash.and_then(|crash| {
+        crash
+            .write_report(pid, &reason, status, peak_rss)
+            .inspect_err(|e| {
+                warn!(
+                    "fandango_ipc: could not write crash report to {}: {e}",
+                    crash.dir.display()
+                )
+            })
+            .ok()
+    });
+    let err = FandangoCallError::WorkerDied {
+        reason,
+        exit_code: status.and_then(|s| s.code()),
+        signal: status.and_then(|s| s.signal()),
+        forensics,
+    };
+    warn!("{err}");
+    err
+}
+
+/// What a crash report records besides what [`ipc_fail`] learns from reaping the worker.
+struct CrashContext<'a> {
+    /// Parent of the report directory.
+    dir: &'a Path,
+    /// The request the worker was answering when it died.
+    request: Option<&'a IpcReq>,
+    stderr_tail: Option<&'a StderrTail>,
+}
+
+impl CrashContext<'_> {
+    /// Write `report.txt`, the request's inputs (`input.bin`, or `input-<i>.bin` for a batch) and
+    /// `stderr.txt` into a new directory under [`Self::dir`], and return its path.
+    fn write_report(
+        &self,
+        pid: Option<u32>,
+        reason: &str,
+        status: Option<ExitStatus>,
+        peak_rss: Option<u64>,
+    ) -> io::Result<PathBuf> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let dir = self.dir.join(match pid {
+            Some(pid) => format!("crash-{}-pid{pid}", now.as_millis()),
+            None => format!("crash-{}-server", now.as_millis()),
+        });
+        std::fs::create_dir_all(&dir)?;
+
+        let mut report = vec![
+            format!("reason: {reason}"),
+            format!("time: {} (unix seconds)", now.as_secs()),
+        ];
+        if let Some(pid) = pid {
+            report.push(format!("pid: {pid}"));
+        }
+        if let Some(code) = status.and_then(|s| s.code()) {
+            report.push(format!("exit code: {code}"));
+        }
+        if let Some(signal) = status.and_then(|s| s.signal()) {
+            report.push(format!("signal: {signal}"));
+        }
+        if let Some(rss) = peak_rss {
+            report.push(format!("peak RSS: {rss} bytes"));
+        }
+        match self.request {
+            Some(req) => report.push(format!("request: {}", req.name())),
+            None => report.push("request: none in flight".to_string()),
+        }
+        match self.request {
+            Some(IpcReq::Parse(input) | IpcReq::ParseTrees(input)) => {
+                std::fs::write(dir.join("input.bin"), input)?;
+            }
+            Some(IpcReq::ParseBatch(inputs)) => {
+                for (i, input) in inputs.iter().enumerate() {
+                    std::fs::write(dir.join(format!("input-{i}.bin")), input)?;
+                }
+            }
+            Some(IpcReq::NextBatch(n)) => report.push(format!("batch size: {n}")),
+            Some(IpcReq::GenerateFromSymbol(symbol)) => report.push(format!("symbol: {symbol}")),
+            Some(IpcReq::Next | IpcReq::NextTree | IpcReq::Shutdown | IpcReq::Health) | None => {}
+        }
+        report.push(String::new());
+        std::fs::write(dir.join("report.txt"), report.join("\n"))?;
+
+        if let Some(tail) = self.stderr_tail {
+            let mut lines = tail.snapshot();
+            lines.push(String::new());
+            std::fs::write(dir.join("stderr.txt"), lines.join("\n"))?;
+        }
+        Ok(dir)
+    }
+}
+
+impl FandangoClient for FandangoSubprocessModule {
+    fn next_input(&mut self) -> Result<Vec<u8>, FandangoCallError> {
+        let resp = if self.pipeline_depth > 0 {
+            self.pipelined_next()?
+        } else {
+            self.rpc(&IpcReq::Next)?
+        };
+        match resp {
+            IpcResp::NextOk(b) => Ok(b),
+            IpcResp::CallErr(e) => Err(e),
+            _ => Err(FandangoCallError::Protocol(
+                "unexpected response to Next from worker".into(),
+            )),
+        }
+    }
+
+    fn parse_input(&mut self, input: &[u8]) -> Result<u32, FandangoCallError> {
+        match self.rpc(&IpcReq::Parse(input.to_vec()))? {
+            IpcResp::ParseOk(n) => Ok(n),
+            IpcResp::CallErr(e) => Err(e),
+            _ => Err(FandangoCallError::Protocol(
+                "unexpected response to Parse from worker".into(),
+            )),
+        }
+    }
+
+    fn next_tree(&mut self) -> Result<DerivationTree, FandangoCallError> {
+        match self.rpc(&IpcReq::NextTree)? {
+            IpcResp::TreeOk(t) => Ok(t),
+            IpcResp::CallErr(e) => Err(e),
+            _ => Err(FandangoCallError::Protocol(
+                "unexpected response to NextTree from worker".into(),
+            )),
+        }
+    }
+
+    fn parse_trees(&mut self, input: &[u8]) -> Result<Vec<DerivationTree>, FandangoCallError> {
+        match self.rpc(&IpcReq::ParseTrees(input.to_vec()))? {
+            IpcResp::TreesOk(t) => Ok(t),
+            IpcResp::CallErr(e) => Err(e),
+            _ => Err(FandangoCallError::Protocol(
+                "unexpected response to ParseTrees from worker".into(),
+            )),
+        }
+    }
+
+    fn generate_from_symbol(&mut self, symbol: &str) -> Result<DerivationTree, FandangoCallError> {
+        match self.rpc(&IpcReq::GenerateFromSymbol(symbol.to_string()))? {
+            IpcResp::TreeOk(t) => Ok(t),
+            IpcResp::CallErr(e) => Err(e),
+            _ => Err(FandangoCallError::Protocol(
+                "unexpected response to GenerateFromSymbol from worker".into(),
+            )),
+        }
+    }
+
+    fn next_inputs(&mut self, n: usize) -> Result<Vec<Vec<u8>>, FandangoCallError> {
+        match self.rpc(&IpcReq::NextBatch(n as u64))? {
+            IpcResp::NextBatchOk(b) if b.len() == n => Ok(b),
+            IpcResp::NextBatchOk(b) => Err(FandangoCallError::Protocol(format!(
+                "worker returned {} inputs, expected {n}",
+                b.len()
+            ))),
+            IpcResp::CallErr(e) => Err(e),
+            _ => Err(FandangoCallError::Protocol(
+                "unexpected response to NextBatch from worker".into(),
+            )),
+        }
+    }
+
+    fn parse_inputs(&mut self, inputs: &[&[u8]]) -> Result<Vec<u32>, FandangoCallError> {
+        let req = IpcReq::ParseBatch(inputs.iter().map(|i| i.to_vec()).collect());
+        match self.rpc(&req)? {
+            IpcResp::ParseBatchOk(c) if c.len() == inputs.len() => Ok(c),
+            IpcResp::ParseBatchOk(c) => Err(FandangoCallError::Protocol(format!(
+                "worker returned {} parse results, expected {}",
+                c.len(),
+                inputs.len()
+            ))),
+            IpcResp::CallErr(e) => Err(e),
+            _ => Err(FandangoCallError::Protocol(
+                "unexpected response to ParseBatch from worker".into(),
+            )),
+        }
+    }
+}
+
+impl Drop for FandangoSubprocessModule {
+    fn drop(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            worker.retire(&self.shutdown_policy, true);
+        }
+    }
+}
+
+#[cfg(feature = "ipc-worker-ctor")]
+#[ctor]
+fn ipc_worker_ctor() {
+    exit_now_if_ipc_worker_argv();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COUNTER_INTERFACE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/fandango/testdata/counter.py"
+    );
+
+    fn numbers(inputs: impl IntoIterator<Item = Vec<u8>>) -> Vec<u64> {
+        inputs
+            .into_iter()
+            .map(|i| String::from_utf8(i).unwrap().parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn restart_backoff_doubles_up_to_the_cap() {
+        let policy = FandangoRestartPolicy {
+            max_restarts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        };
+        let waits: Vec<_> = (0..6).map(|n| policy.backoff(n).as_millis()).collect();
+        assert_eq!(waits, [100, 200, 400, 800, 1000, 1000]);
+    }
+
+    #[test]
+    fn restart_backoff_saturates_for_long_streaks() {
+        let policy = FandangoRestartPolicy::default();
+        assert_eq!(policy.backoff(31), policy.max_backoff);
+        assert_eq!(policy.backoff(u32::MAX), policy.max_backoff);
+    }
+
+    #[test]
+    fn recycling_after_every_request_fails_no_call() {
+        let mut module = FandangoSubprocessModule::builder("unused.fan", &[])
+            .python_interface(COUNTER_INTERFACE)
+            .pipeline_depth(2)
+            .recycle_policy(Some(FandangoRecyclePolicy {
+                max_requests: Some(1),
+                max_rss: None,
+            }))
+            .build()
+            .unwrap();
+        // Every request after the first drains the pipeline and replaces the worker.
+        for _ in 0..4 {
+            module.next_input().unwrap();
+            assert_eq!(module.parse_input(b"12").unwrap(), 1);
+        }
+        assert!(module.recycle_count() >= 8, "{}", module.recycle_count());
+        assert_eq!(module.restart_count(), 0);
+    }
+
+    #[test]
+    fn call_timeout_covers_a_request_the_worker_does_not_read() {
+        let mut module = FandangoSubprocessModule::builder("unused.fan", &[])
+            .python_interface(COUNTER_INTERFACE)
+            .call_timeout(Some(Duration::from_millis(500)))
+            .build()
+            .unwrap();
+        let pid = module.health().unwrap().pid;
+        // A stopped worker never drains the socket, so a large request fills its buffer.
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGSTOP) };
+        let start = Instant::now();
+        let result = module.parse_input(&vec![b'1'; 16 << 20]);
+        assert!(
+            matches!(result, Err(FandangoCallError::Timeout(_))),
+            "{result:?}"
+        );
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+
+    #[test]
+    fn zygote_forks_every_worker_from_the_state_after_setup() {
+        let mut module = FandangoSubprocessModule::builder("unused.fan", &[])
+            .python_interface(COUNTER_INTERFACE)
+            .zygote(true)
+            .restart_policy(Some(FandangoRestartPolicy {
+                initial_backoff: Duration::ZERO,
+                ..Default::default()
+            }))
+            .build()
+            .unwrap();
+        assert_eq!(numbers([module.next_input().unwrap()]), [1]);
+        assert_eq!(numbers([module.next_input().unwrap()]), [2]);
+        let first = module.health().unwrap().pid;
+        unsafe { libc::kill(first as libc::pid_t, libc::SIGKILL) };
+        let died = module.next_input();
+        assert!(
+            matches!(died, Err(FandangoCallError::WorkerDied { .. })),
+            "{died:?}"
+        );
+        // The replacement is a fresh fork, so it counts from 1 again.
+        assert_eq!(numbers([module.next_input().unwrap()]), [1]);
+        assert_ne!(module.health().unwrap().pid, first);
+        assert_eq!(module.restart_count(), 1);
+    }
+
+    #[test]
+    fn unix_socket_server_serializes_concurrent_clients() {
+        let path = std::env::temp_dir().join(format!(
+            "fandango-test-{}-concurrent.sock",
+            std::process::id()
+        ));
+        let server_path = path.clone();
+        thread::spawn(move || {
+            serve_unix_socket(
+                &server_path,
+                COUNTER_INTERFACE,
+                "unused.fan",
+                &[("spin", "300000")],
+            )
+        });
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let connect = || loop {
+            match FandangoSubprocessModule::connect(&path) {
+                Ok(module) => return module,
+                Err(_) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+                Err(e) => panic!("{e}"),
+            }
+        };
+
+        // Both connections exist before either thread starts calling, so their requests overlap.
+        let clients: Vec<_> = (0..2).map(|_| connect()).collect();
+        let threads: Vec<_> = clients
+            .into_iter()
+            .map(|mut module| {
+                thread::spawn(move || {
+                    (0..50)
+                        .map(|_| module.next_input())
+                        .collect::<Result<Vec<_>, _>>()
+                })
+            })
+            .collect();
+        let mut all = Vec::new();
+        for t in threads {
+            all.extend(numbers(t.join().unwrap().unwrap()));
+        }
+        let _ = std::fs::remove_file(&path);
+        // Every number once: the clients took turns on the one generator.
+        all.sort_unstable();
+        assert_eq!(all, (1..=100).collect::<Vec<_>>());
+    }
 }
@@ -0,0 +1,34 @@
+"""Stand-in for `examples/run_fandango.py` in unit tests, so they run without Fandango installed.
+
+`next_input` counts up from 1 through a single generator, the way Fandango's inputs come out of
+one. The `spin` kwarg adds busy work inside the generator, long enough for the interpreter to
+switch threads in the middle of it.
+"""
+
+from typing import Any
+
+
+class Counter:
+    def __init__(self, kwargs: dict[str, Any]):
+        self.spin = int(kwargs.get("spin", "0"))
+        self.numbers = self._count()
+
+    def _count(self):
+        i = 0
+        while True:
+            i += 1
+            for _ in range(self.spin):
+                pass
+            yield str(i).encode()
+
+
+def setup(fan_file: str, kwargs: dict[str, Any]) -> Counter:
+    return Counter(kwargs)
+
+
+def next_input(counter: Counter) -> bytes:
+    return next(counter.numbers)
+
+
+def parse_input(counter: Counter, input: bytes) -> int:
+    return 1 if input.isdigit() else 0
@@ -0,0 +1,253 @@
+use std::ops::Range;
+
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A Fandango derivation tree, detached from the Python interpreter.
+///
+/// The Python interface hands trees over as nested `(symbol, terminal, children)` tuples, where
+/// `terminal` is the leaf's bytes (or `None` for nonterminals); spans are computed on the Rust side.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DerivationTree {
+    /// Nonterminal name including angle brackets (e.g. `<start>`), or the terminal as written in the spec.
+    pub symbol: String,
+    /// Bytes of a terminal leaf; `None` for nonterminals.
+    pub terminal: Option<Vec<u8>>,
+    pub children: Vec<DerivationTree>,
+    /// Byte range this node covers in [`DerivationTree::to_bytes`] of the root.
+    pub span: Range<usize>,
+}
+
+impl DerivationTree {
+    pub fn is_terminal(&self) -> bool {
+        self.terminal.is_some()
+    }
+
+    pub fn is_nonterminal(&self) -> bool {
+        self.terminal.is_none()
+    }
+
+    /// Unparse the tree by concatenating its terminal leaves.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.span.len());
+        self.write_bytes(&mut out);
+        out
+    }
+
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        match &self.terminal {
+            Some(bytes) => out.extend_from_slice(bytes),
+            None => self.children.iter().for_each(|c| c.write_bytes(out)),
+        }
+    }
+
+    /// All nodes in pre-order (the root is index 0).
+    pub fn iter(&self) -> impl Iterator<Item = &DerivationTree> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(node.children.iter().rev());
+            Some(node)
+        })
+    }
+
+    /// The node at `index` in [`DerivationTree::iter`] order.
+    pub fn node_mut(&mut self, index: usize) -> Option<&mut DerivationTree> {
+        let mut remaining = index;
+        self.node_mut_at(&mut remaining)
+    }
+
+    fn node_mut_at(&mut self, remaining: &mut usize) -> Option<&mut DerivationTree> {
+        if *remaining == 0 {
+            return Some(self);
+        }
+        *remaining -= 1;
+        for child in &mut self.children {
+            if let Some(node) = child.node_mut_at(remaining) {
+                return Some(node);
+            }
+        }
+        None
+    }
+
+    /// Replace the node at `index` (in [`DerivationTree::iter`] order) with `subtree` and update all spans.
+    ///
+    /// Returns the replaced node, or `None` (leaving `self` untouched) if `index` is out of range.
+    pub fn replace_node(
+        &mut self,
+        index: usize,
+        subtree: DerivationTree,
+    ) -> Option<DerivationTree> {
+        let old = std::mem::replace(self.node_mut(index)?, subtree);
+        self.recompute_spans();
+        Some(old)
+    }
+
+    /// Recompute every node's span from the terminal lengths, e.g. after editing the tree by hand.
+    pub fn recompute_spans(&mut self) {
+        self.recompute_spans_at(&mut 0);
+    }
+
+    fn recompute_spans_at(&mut self, offset: &mut usize) {
+        let start = *offset;
+        match &self.terminal {
+            Some(bytes) => *offset += bytes.len(),
+            None => self
+                .children
+                .iter_mut()
+                .for_each(|c| c.recompute_spans_at(offset)),
+        }
+        self.span = start..*offset;
+    }
+
+    /// Convert a nested `(symbol, terminal, children)` tuple produced by the Python interface.
+    pub(crate) fn from_py(obj: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Self::from_py_at(obj, &mut 0)
+    }
+
+    fn from_py_at(obj: &Bound<'_, PyAny>, offset: &mut usize) -> PyResult<Self> {
+        let (symbol, terminal, children): (String, Option<Vec<u8>>, Vec<Bound<'_, PyAny>>) =
+            obj.extract()?;
+        let start = *offset;
+        let children = match &terminal {
+            Some(bytes) => {
+                *offset += bytes.len();
+                Vec::new()
+            }
+            None => children
+                .iter()
+                .map(|c| Self::from_py_at(c, offset))
+                .collect::<PyResult<_>>()?,
+        };
+        Ok(Self {
+            symbol,
+            terminal,
+            children,
+            span: start..*offset,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(symbol: &str, bytes: &[u8]) -> DerivationTree {
+        DerivationTree {
+            symbol: symbol.to_string(),
+            terminal: Some(bytes.to_vec()),
+            children: Vec::new(),
+            span: 0..0,
+        }
+    }
+
+    fn node(symbol: &str, children: Vec<DerivationTree>) -> DerivationTree {
+        DerivationTree {
+            symbol: symbol.to_string(),
+            terminal: None,
+            children,
+            span: 0..0,
+        }
+    }
+
+    /// `<start>` (0) -> `<a>` (1) -> `'x'` (2), `<b>` (3) -> `'yz'` (4)
+    fn sample() -> DerivationTree {
+        let mut tree = node(
+            "<start>",
+            vec![
+                node("<a>", vec![leaf("'x'", b"x")]),
+                node("<b>", vec![leaf("'yz'", b"yz")]),
+            ],
+        );
+        tree.span = 0..3;
+        tree.children[0].span = 0..1;
+        tree.children[0].children[0].span = 0..1;
+        tree.children[1].span = 1..3;
+        tree.children[1].children[0].span = 1..3;
+        tree
+    }
+
+    fn assert_spans_match_bytes(tree: &DerivationTree) {
+        let bytes = tree.to_bytes();
+        assert_eq!(tree.span, 0..bytes.len());
+        for node in tree.iter() {
+            assert_eq!(bytes[node.span.clone()], node.to_bytes(), "{}", node.symbol);
+        }
+    }
+
+    fn symbols(tree: &DerivationTree) -> Vec<&str> {
+        tree.iter().map(|n| n.symbol.as_str()).collect()
+    }
+
+    #[test]
+    fn iter_is_pre_order() {
+        let tree = sample();
+        assert_eq!(symbols(&tree), ["<start>", "<a>", "'x'", "<b>", "'yz'"]);
+        assert_spans_match_bytes(&tree);
+        assert_eq!(tree.children[1].span, 1..3);
+    }
+
+    #[test]
+    fn node_mut_follows_iter_order() {
+        let mut tree = sample();
+        let expected: Vec<String> = tree.iter().map(|n| n.symbol.clone()).collect();
+        for (i, symbol) in expected.iter().enumerate() {
+            assert_eq!(&tree.node_mut(i).unwrap().symbol, symbol);
+        }
+        assert!(tree.node_mut(expected.len()).is_none());
+    }
+
+    #[test]
+    fn replace_root() {
+        let mut tree = sample();
+        let old = tree.replace_node(0, leaf("'q'", b"qq")).unwrap();
+        assert_eq!(old, sample());
+        assert_eq!(tree.to_bytes(), b"qq");
+        assert_eq!(tree.span, 0..2);
+        assert_spans_match_bytes(&tree);
+    }
+
+    #[test]
+    fn replace_middle_node() {
+        let mut tree = sample();
+        let old = tree
+            .replace_node(1, node("<a>", vec![leaf("'w'", b"www")]))
+            .unwrap();
+        assert_eq!(old.to_bytes(), b"x");
+        assert_eq!(tree.to_bytes(), b"wwwyz");
+        assert_eq!(tree.children[1].span, 3..5);
+        assert_spans_match_bytes(&tree);
+    }
+
+    #[test]
+    fn replace_last_leaf() {
+        let mut tree = sample();
+        let old = tree.replace_node(4, leaf("''", b"")).unwrap();
+        assert_eq!(old.span, 1..3);
+        assert_eq!(tree.to_bytes(), b"x");
+        assert_eq!(tree.children[1].span, 1..1);
+        assert_spans_match_bytes(&tree);
+    }
+
+    #[test]
+    fn replace_out_of_range_leaves_tree_unchanged() {
+        let mut tree = sample();
+        assert!(tree.replace_node(5, leaf("'q'", b"q")).is_none());
+        assert_eq!(tree, sample());
+    }
+
+    #[test]
+    fn from_py_computes_spans() {
+        let tree = Python::with_gil(|py| {
+            let obj = py
+                .eval(
+                    c"('<start>', None, [('<a>', None, [(\"'x'\", b'x', [])]), ('<b>', None, [(\"'yz'\", b'yz', [])])])",
+                    None,
+                    None,
+                )
+                .unwrap();
+            DerivationTree::from_py(&obj).unwrap()
+        });
+        assert_eq!(tree, sample());
+    }
+}
@@ -0,0 +1,139 @@
+//! Deadline enforcement for in-process Python calls.
+//!
+//! A single background thread per [`Watchdog`] waits for the deadline of the call that is
+//! currently armed. When it passes, the thread takes the GIL and raises `KeyboardInterrupt` in the
+//! calling thread with `PyThreadState_SetAsyncExc`. Unlike `PyErr_SetInterrupt`, this also works
+//! when the fuzzer calls Fandango from a thread other than the main one.
+//!
+//! Lock order is always GIL, then state: the caller arms and disarms while holding the GIL, and the
+//! watchdog only inspects the state after acquiring it, so an interrupt never lands after the call
+//! has been disarmed.
+
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::warn;
+use pyo3::ffi;
+use pyo3::prelude::*;
+
+#[derive(Default)]
+struct State {
+    /// Deadline and Python thread id of the call being watched, with its generation.
+    armed: Option<(Instant, libc::c_long, u64)>,
+    generation: u64,
+    /// Set when the interrupt for the armed generation has been raised.
+    fired: bool,
+    shutdown: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // The state is plain data, so it is consistent even if a holder panicked.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Interrupts Python calls that run past their deadline.
+pub(crate) struct Watchdog {
+    shared: Arc<Shared>,
+}
+
+impl Watchdog {
+    pub(crate) fn new() -> io::Result<Self> {
+        let shared = Arc::new(Shared::default());
+        let thread_shared = Arc::clone(&shared);
+        thread::Builder::new()
+            .name("fandango-watchdog".to_string())
+            .spawn(move || run(&thread_shared))?;
+        Ok(Self { shared })
+    }
+
+    /// Run `f` with the GIL held, interrupting it once `timeout` has passed.
+    ///
+    /// Returns `f`'s result and whether the interrupt was raised. An interrupt that was raised but
+    /// not yet delivered when `f` returned is cleared.
+    pub(crate) fn call<T>(
+        &self,
+        py: Python<'_>,
+        timeout: Duration,
+        f: impl FnOnce(Python<'_>) -> T,
+    ) -> (T, bool) {
+        // SAFETY: pthread_self has no preconditions; CPython uses it as the thread id on Unix.
+        let thread_id = unsafe { libc::pthread_self() } as libc::c_long;
+        let generation = {
+            let mut state = self.shared.lock();
+            state.generation += 1;
+            state.fired = false;
+            state.armed = Some((Instant::now() + timeout, thread_id, state.generation));
+            state.generation
+        };
+        self.shared.cond.notify_one();
+
+        let ret = f(py);
+
+        let mut state = self.shared.lock();
+        let fired = state.fired && state.armed.is_some_and(|(.., g)| g == generation);
+        state.armed = None;
+        state.fired = false;
+        drop(state);
+        if fired {
+            // SAFETY: we hold the GIL; a NULL exception clears a pending async exception.
+            unsafe { ffi::PyThreadState_SetAsyncExc(thread_id, std::ptr::null_mut()) };
+        }
+        (ret, fired)
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        // Not joined: the thread may be waiting for the GIL, which the dropping thread could hold.
+        self.shared.lock().shutdown = true;
+        self.shared.cond.notify_one();
+    }
+}
+
+fn run(shared: &Shared) {
+    let mut state = shared.lock();
+    loop {
+        if state.shutdown {
+            return;
+        }
+        let Some((deadline, thread_id, generation)) = state.armed.filter(|_| !state.fired) else {
+            state = shared.cond.wait(state).unwrap_or_else(|e| e.into_inner());
+            continue;
+        };
+        let now = Instant::now();
+        if now < deadline {
+            state = shared
+                .cond
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+            continue;
+        }
+
+        drop(state);
+        Python::with_gil(|_py| {
+            let mut state = shared.lock();
+            if state.armed.is_some_and(|(.., g)| g == generation) && !state.fired {
+                // SAFETY: we hold the GIL and pass a borrowed reference to a builtin exception type.
+                let n = unsafe {
+                    ffi::PyThreadState_SetAsyncExc(thread_id, ffi::PyExc_KeyboardInterrupt)
+                };
+                if n != 1 {
+                    warn!("fandango: could not interrupt Python thread {thread_id} (found {n})");
+                }
+                state.fired = true;
+            }
+        });
+        state = shared.lock();
+    }
+}
@@ -40,10 +40,7 @@ where
         _mgr: &mut EM,
         input: &BytesInput,
     ) -> Result<libafl::executors::ExitKind, Error> {
-        let num_parses = self
-            .fandango
-            .parse_input(&input.target_bytes())
-            .map_err(|e| Error::illegal_state(format!("Fandango error: {e}")))?;
+        let num_parses = self.fandango.parse_input(&input.target_bytes())?;
 
         self.observers
             .get_mut(&self.num_parses_observer)
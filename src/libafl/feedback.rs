@@ -0,0 +1,229 @@
+use std::borrow::Cow;
+
+use libafl::{
+    Error, HasMetadata,
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    inputs::HasTargetBytes,
+    observers::RefCellValueObserver,
+};
+use libafl_bolts::{
+    Named, SerdeAny,
+    tuples::{Handle, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::fandango::FandangoClient;
+
+/// Where a Fandango feedback gets the number of parses of the current input from.
+///
+/// Implemented for every [`FandangoClient`] (the input is parsed on demand) and for the
+/// `RefCellValueObserver<u32>` handle that [`FandangoParseExecutor`](crate::libafl::FandangoParseExecutor)
+/// writes to (no extra parse).
+pub trait FandangoParseCountSource {
+    fn num_parses<OT: MatchNameRef>(&mut self, input: &[u8], observers: &OT) -> Result<u32, Error>;
+}
+
+impl<F: FandangoClient> FandangoParseCountSource for F {
+    fn num_parses<OT: MatchNameRef>(
+        &mut self,
+        input: &[u8],
+        _observers: &OT,
+    ) -> Result<u32, Error> {
+        Ok(self.parse_input(input)?)
+    }
+}
+
+impl<'a> FandangoParseCountSource for Handle<RefCellValueObserver<'a, u32>> {
+    fn num_parses<OT: MatchNameRef>(
+        &mut self,
+        _input: &[u8],
+        observers: &OT,
+    ) -> Result<u32, Error> {
+        Ok(*observers
+            .get(self)
+            .ok_or(Error::illegal_state(format!(
+                "num_parses observer {} not found",
+                self.name()
+            )))?
+            .get_ref())
+    }
+}
+
+/// Which inputs a [`FandangoValidityFeedback`] considers interesting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FandangoValidityMode {
+    /// Interesting if the spec accepts the input; use as a filter to only keep spec-valid inputs.
+    Valid,
+    /// Interesting if the spec rejects the input; use as an objective.
+    Invalid,
+}
+
+/// A feedback that asks Fandango whether the spec accepts the input.
+///
+/// Combine it with other feedbacks using `feedback_and!`/`feedback_or!`, e.g.
+/// `feedback_and_fast!(FandangoValidityFeedback::valid(client), MaxMapFeedback::new(&observer))`
+/// to only keep spec-valid inputs that also increase coverage.
+pub struct FandangoValidityFeedback<P> {
+    source: P,
+    mode: FandangoValidityMode,
+}
+
+impl<P> FandangoValidityFeedback<P> {
+    pub fn new(source: P, mode: FandangoValidityMode) -> Self {
+        Self { source, mode }
+    }
+
+    /// Interesting if the spec accepts the input.
+    pub fn valid(source: P) -> Self {
+        Self::new(source, FandangoValidityMode::Valid)
+    }
+
+    /// Interesting if the spec rejects the input.
+    pub fn invalid(source: P) -> Self {
+        Self::new(source, FandangoValidityMode::Invalid)
+    }
+}
+
+impl<P, S> StateInitializer<S> for FandangoValidityFeedback<P> {}
+
+impl<EM, I, OT, P, S> Feedback<EM, I, OT, S> for FandangoValidityFeedback<P>
+where
+    I: HasTargetBytes,
+    OT: MatchNameRef,
+    P: FandangoParseCountSource,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let accepted = self.source.num_parses(&input.target_bytes(), observers)? > 0;
+        Ok(match self.mode {
+            FandangoValidityMode::Valid => accepted,
+            FandangoValidityMode::Invalid => !accepted,
+        })
+    }
+}
+
+impl<P> Named for FandangoValidityFeedback<P> {
+    fn name(&self) -> &Cow<'static, str> {
+        match self.mode {
+            FandangoValidityMode::Valid => &Cow::Borrowed("FandangoValidityFeedback(valid)"),
+            FandangoValidityMode::Invalid => &Cow::Borrowed("FandangoValidityFeedback(invalid)"),
+        }
+    }
+}
+
+/// How the target and the spec disagree about an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FandangoAcceptanceDiff {
+    /// The target accepts an input that the spec rejects.
+    OverAcceptance,
+    /// The target rejects an input that the spec accepts.
+    UnderAcceptance,
+}
+
+/// Testcase metadata written by [`FandangoAcceptanceDiffFeedback`].
+#[derive(SerdeAny, Debug, Clone, Serialize, Deserialize)]
+pub struct FandangoAcceptanceDiffMetadata {
+    pub diff: FandangoAcceptanceDiff,
+    pub target_accepts: bool,
+    pub num_parses: u32,
+}
+
+/// A differential feedback that is interesting whenever the target and the spec disagree on
+/// whether an input is valid.
+///
+/// The harness reports acceptance through a `RefCellValueObserver<bool>`; the spec's opinion comes
+/// from a [`FandangoParseCountSource`]. Which way they disagree is recorded as
+/// [`FandangoAcceptanceDiffMetadata`] so objectives can be triaged into over- and under-acceptance bugs.
+pub struct FandangoAcceptanceDiffFeedback<'a, P> {
+    target_accepts: Handle<RefCellValueObserver<'a, bool>>,
+    spec: P,
+}
+
+impl<'a, P> FandangoAcceptanceDiffFeedback<'a, P> {
+    pub fn new(target_accepts: Handle<RefCellValueObserver<'a, bool>>, spec: P) -> Self {
+        Self {
+            target_accepts,
+            spec,
+        }
+    }
+}
+
+impl<'a, P: FandangoParseCountSource> FandangoAcceptanceDiffFeedback<'a, P> {
+    fn diff<OT: MatchNameRef>(
+        &mut self,
+        input: &[u8],
+        observers: &OT,
+    ) -> Result<Option<FandangoAcceptanceDiffMetadata>, Error> {
+        let target_accepts = *observers
+            .get(&self.target_accepts)
+            .ok_or(Error::illegal_state(format!(
+                "target acceptance observer {} not found",
+                self.target_accepts.name()
+            )))?
+            .get_ref();
+        let num_parses = self.spec.num_parses(input, observers)?;
+        let diff = match (target_accepts, num_parses > 0) {
+            (true, false) => FandangoAcceptanceDiff::OverAcceptance,
+            (false, true) => FandangoAcceptanceDiff::UnderAcceptance,
+            _ => return Ok(None),
+        };
+        Ok(Some(FandangoAcceptanceDiffMetadata {
+            diff,
+            target_accepts,
+            num_parses,
+        }))
+    }
+}
+
+impl<'a, P, S> StateInitializer<S> for FandangoAcceptanceDiffFeedback<'a, P> {}
+
+impl<'a, EM, I, OT, P, S> Feedback<EM, I, OT, S> for FandangoAcceptanceDiffFeedback<'a, P>
+where
+    I: HasTargetBytes,
+    OT: MatchNameRef,
+    P: FandangoParseCountSource,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        Ok(self.diff(&input.target_bytes(), observers)?.is_some())
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        // Recomputed instead of cached: with `feedback_or_fast!`, `is_interesting` may not have run
+        // for this execution. This only happens for solutions, so the extra parse is cheap overall.
+        let Some(input) = testcase.input() else {
+            return Ok(());
+        };
+        let bytes = input.target_bytes().to_vec();
+        if let Some(meta) = self.diff(&bytes, observers)? {
+            testcase.add_metadata(meta);
+        }
+        Ok(())
+    }
+}
+
+impl<'a, P> Named for FandangoAcceptanceDiffFeedback<'a, P> {
+    fn name(&self) -> &Cow<'static, str> {
+        &Cow::Borrowed("FandangoAcceptanceDiffFeedback")
+    }
+}
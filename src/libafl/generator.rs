@@ -1,6 +1,6 @@
 use libafl::{Error, generators::Generator, inputs::BytesInput};
 
-use crate::fandango::FandangoClient;
+use crate::{fandango::FandangoClient, libafl::FandangoTreeInput};
 
 pub struct FandangoGenerator<F> {
     fandango: F,
@@ -14,10 +14,25 @@ impl<F> FandangoGenerator<F> {
 
 impl<F: FandangoClient, S> Generator<BytesInput, S> for FandangoGenerator<F> {
     fn generate(&mut self, _state: &mut S) -> Result<BytesInput, Error> {
-        let input = self
-            .fandango
-            .next_input()
-            .map_err(|e| Error::illegal_state(format!("Fandango error: {e}")))?;
+        let input = self.fandango.next_input()?;
         Ok(input.into())
     }
 }
+
+/// Like [`FandangoGenerator`], but produces [`FandangoTreeInput`]s using [`FandangoClient::next_tree`].
+pub struct FandangoTreeGenerator<F> {
+    fandango: F,
+}
+
+impl<F> FandangoTreeGenerator<F> {
+    pub fn new(fandango: F) -> Self {
+        Self { fandango }
+    }
+}
+
+impl<F: FandangoClient, S> Generator<FandangoTreeInput, S> for FandangoTreeGenerator<F> {
+    fn generate(&mut self, _state: &mut S) -> Result<FandangoTreeInput, Error> {
+        let tree = self.fandango.next_tree()?;
+        Ok(tree.into())
+    }
+}
@@ -0,0 +1,52 @@
+use libafl::inputs::{HasTargetBytes, Input};
+use libafl_bolts::{HasLen, ownedref::OwnedSlice};
+use serde::{Deserialize, Serialize};
+
+use crate::fandango::DerivationTree;
+
+/// A LibAFL input that keeps the derivation tree Fandango produced.
+///
+/// The target sees the unparsed bytes; the corpus (including [`OnDiskCorpus`](libafl::corpus::OnDiskCorpus))
+/// stores the whole tree, so it survives a reload.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FandangoTreeInput {
+    tree: DerivationTree,
+}
+
+impl FandangoTreeInput {
+    pub fn new(tree: DerivationTree) -> Self {
+        Self { tree }
+    }
+
+    pub fn tree(&self) -> &DerivationTree {
+        &self.tree
+    }
+
+    pub fn tree_mut(&mut self) -> &mut DerivationTree {
+        &mut self.tree
+    }
+
+    pub fn into_tree(self) -> DerivationTree {
+        self.tree
+    }
+}
+
+impl From<DerivationTree> for FandangoTreeInput {
+    fn from(tree: DerivationTree) -> Self {
+        Self::new(tree)
+    }
+}
+
+impl Input for FandangoTreeInput {}
+
+impl HasTargetBytes for FandangoTreeInput {
+    fn target_bytes(&self) -> OwnedSlice<'_, u8> {
+        OwnedSlice::from(self.tree.to_bytes())
+    }
+}
+
+impl HasLen for FandangoTreeInput {
+    fn len(&self) -> usize {
+        self.tree.span.len()
+    }
+}
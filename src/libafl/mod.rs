@@ -1,9 +1,18 @@
 pub(crate) mod executor;
+pub(crate) mod feedback;
 pub(crate) mod generator;
+pub(crate) mod input;
 pub(crate) mod mutator;
+pub(crate) mod observer;
 pub(crate) mod stage;
 
 pub use executor::FandangoParseExecutor;
-pub use generator::FandangoGenerator;
-pub use mutator::FandangoPseudoMutator;
+pub use feedback::{
+    FandangoAcceptanceDiff, FandangoAcceptanceDiffFeedback, FandangoAcceptanceDiffMetadata,
+    FandangoParseCountSource, FandangoValidityFeedback, FandangoValidityMode,
+};
+pub use generator::{FandangoGenerator, FandangoTreeGenerator};
+pub use input::FandangoTreeInput;
+pub use mutator::{FandangoPseudoMutator, FandangoSpliceMutator, FandangoSubtreeMutator};
+pub use observer::{FandangoGrammarCoverageObserver, GrammarCoverageGranularity};
 pub use stage::FandangoPostMutationalStage;
@@ -1,14 +1,19 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashMap};
 
 use libafl::{
     Error,
-    corpus::CorpusId,
-    inputs::BytesInput,
+    corpus::{Corpus, CorpusId},
+    inputs::{BytesInput, HasTargetBytes as _},
     mutators::{MutationResult, Mutator},
+    random_corpus_id_with_disabled,
+    state::{HasCorpus, HasRand},
 };
-use libafl_bolts::Named;
+use libafl_bolts::{Named, rands::Rand as _};
 
-use crate::fandango::FandangoClient;
+use crate::{
+    fandango::{DerivationTree, FandangoClient},
+    libafl::FandangoTreeInput,
+};
 
 pub struct FandangoPseudoMutator<F> {
     fandango: F,
@@ -22,10 +27,7 @@ impl<F> FandangoPseudoMutator<F> {
 
 impl<F: FandangoClient, S> Mutator<BytesInput, S> for FandangoPseudoMutator<F> {
     fn mutate(&mut self, _state: &mut S, input: &mut BytesInput) -> Result<MutationResult, Error> {
-        let new_input = self
-            .fandango
-            .next_input()
-            .map_err(|e| Error::illegal_state(format!("Fandango error: {e}")))?;
+        let new_input = self.fandango.next_input()?;
         *input = BytesInput::new(new_input);
         Ok(MutationResult::Mutated)
     }
@@ -40,3 +42,255 @@ impl<F> Named for FandangoPseudoMutator<F> {
         &Cow::Borrowed("FandangoPseudoMutator")
     }
 }
+
+/// Structural mutator: picks a random nonterminal node of the input's derivation tree and replaces
+/// it with a fresh expansion from [`FandangoClient::generate_from_symbol`], keeping the rest of the input.
+///
+/// The root is only picked when it is the tree's only nonterminal, since regenerating it throws the
+/// whole input away.
+///
+/// [`BytesInput`]s are parsed first and skipped if the spec rejects them; [`FandangoTreeInput`]s are
+/// mutated directly.
+pub struct FandangoSubtreeMutator<F> {
+    fandango: F,
+}
+
+impl<F> FandangoSubtreeMutator<F> {
+    pub fn new(fandango: F) -> Self {
+        Self { fandango }
+    }
+}
+
+impl<F: FandangoClient> FandangoSubtreeMutator<F> {
+    fn regenerate_subtree<S: HasRand>(
+        &mut self,
+        state: &mut S,
+        tree: &mut DerivationTree,
+    ) -> Result<MutationResult, Error> {
+        let candidates = tree
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, node)| node.is_nonterminal())
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let index = match state.rand_mut().choose(candidates) {
+            Some(index) => index,
+            None if tree.is_nonterminal() => 0,
+            None => return Ok(MutationResult::Skipped),
+        };
+        let symbol = tree
+            .iter()
+            .nth(index)
+            .expect("index from iter")
+            .symbol
+            .clone();
+        let subtree = self.fandango.generate_from_symbol(&symbol)?;
+        tree.replace_node(index, subtree);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl<F: FandangoClient, S: HasRand> Mutator<BytesInput, S> for FandangoSubtreeMutator<F> {
+    fn mutate(&mut self, state: &mut S, input: &mut BytesInput) -> Result<MutationResult, Error> {
+        let trees = self.fandango.parse_trees(&input.target_bytes())?;
+        let Some(mut tree) = state.rand_mut().choose(trees) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let res = self.regenerate_subtree(state, &mut tree)?;
+        if res == MutationResult::Mutated {
+            *input = BytesInput::new(tree.to_bytes());
+        }
+        Ok(res)
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<F: FandangoClient, S: HasRand> Mutator<FandangoTreeInput, S> for FandangoSubtreeMutator<F> {
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut FandangoTreeInput,
+    ) -> Result<MutationResult, Error> {
+        self.regenerate_subtree(state, input.tree_mut())
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<F> Named for FandangoSubtreeMutator<F> {
+    fn name(&self) -> &Cow<'static, str> {
+        &Cow::Borrowed("FandangoSubtreeMutator")
+    }
+}
+
+/// Grammar-aware crossover: parses the current input and a random other corpus entry, then replaces a
+/// random subtree of the current input with a subtree of the other input rooted at the same nonterminal.
+///
+/// Similar to Nautilus splicing, but uses Fandango's parser instead of a separate grammar. Skipped if
+/// either input does not parse or they share no nonterminal.
+pub struct FandangoSpliceMutator<F> {
+    fandango: F,
+}
+
+impl<F> FandangoSpliceMutator<F> {
+    pub fn new(fandango: F) -> Self {
+        Self { fandango }
+    }
+}
+
+impl<F, S> Mutator<BytesInput, S> for FandangoSpliceMutator<F>
+where
+    F: FandangoClient,
+    S: HasCorpus<BytesInput> + HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut BytesInput) -> Result<MutationResult, Error> {
+        let id = random_corpus_id_with_disabled!(state.corpus(), state.rand_mut());
+        // Splicing an input with itself cannot produce anything new
+        if let Some(cur) = state.corpus().current()
+            && id == *cur
+        {
+            return Ok(MutationResult::Skipped);
+        }
+        let other_bytes = {
+            let mut other_testcase = state.corpus().get_from_all(id)?.borrow_mut();
+            other_testcase
+                .load_input(state.corpus())?
+                .target_bytes()
+                .to_vec()
+        };
+
+        let Some(mut tree) = state
+            .rand_mut()
+            .choose(self.fandango.parse_trees(&input.target_bytes())?)
+        else {
+            return Ok(MutationResult::Skipped);
+        };
+        let Some(other_tree) = state
+            .rand_mut()
+            .choose(self.fandango.parse_trees(&other_bytes)?)
+        else {
+            return Ok(MutationResult::Skipped);
+        };
+
+        let mut donors: HashMap<&str, Vec<&DerivationTree>> = HashMap::new();
+        for node in other_tree.iter().filter(|n| n.is_nonterminal()) {
+            donors.entry(node.symbol.as_str()).or_default().push(node);
+        }
+        let candidates = tree
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.is_nonterminal() && donors.contains_key(node.symbol.as_str()))
+            .map(|(i, node)| (i, node.symbol.clone()))
+            .collect::<Vec<_>>();
+        let Some((index, symbol)) = state.rand_mut().choose(candidates) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let donor = state
+            .rand_mut()
+            .choose(&donors[symbol.as_str()])
+            .expect("donor lists are never empty");
+
+        tree.replace_node(index, (*donor).clone());
+        *input = BytesInput::new(tree.to_bytes());
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<F> Named for FandangoSpliceMutator<F> {
+    fn name(&self) -> &Cow<'static, str> {
+        &Cow::Borrowed("FandangoSpliceMutator")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl::state::NopState;
+
+    use super::*;
+    use crate::fandango::FandangoCallError;
+
+    /// Expands every nonterminal to a single `x` and records which symbols were asked for.
+    #[derive(Default)]
+    struct Regenerate {
+        symbols: Vec<String>,
+    }
+
+    impl FandangoClient for Regenerate {
+        fn next_input(&mut self) -> Result<Vec<u8>, FandangoCallError> {
+            Err(FandangoCallError::Exhausted)
+        }
+
+        fn parse_input(&mut self, _input: &[u8]) -> Result<u32, FandangoCallError> {
+            Ok(1)
+        }
+
+        fn generate_from_symbol(
+            &mut self,
+            symbol: &str,
+        ) -> Result<DerivationTree, FandangoCallError> {
+            self.symbols.push(symbol.to_string());
+            Ok(node(symbol, vec![leaf(b'x')]))
+        }
+    }
+
+    fn leaf(b: u8) -> DerivationTree {
+        DerivationTree {
+            symbol: format!("'{}'", b as char),
+            terminal: Some(vec![b]),
+            children: Vec::new(),
+            span: 0..1,
+        }
+    }
+
+    fn node(symbol: &str, children: Vec<DerivationTree>) -> DerivationTree {
+        let mut tree = DerivationTree {
+            symbol: symbol.to_string(),
+            terminal: None,
+            children,
+            span: 0..0,
+        };
+        tree.recompute_spans();
+        tree
+    }
+
+    #[test]
+    fn subtree_mutator_keeps_the_root() {
+        let mut mutator = FandangoSubtreeMutator::new(Regenerate::default());
+        let mut state: NopState<FandangoTreeInput> = NopState::new();
+        for _ in 0..32 {
+            let mut input = FandangoTreeInput::new(node(
+                "<start>",
+                vec![
+                    node("<digit>", vec![leaf(b'1')]),
+                    node("<digit>", vec![leaf(b'2')]),
+                ],
+            ));
+            let res = mutator.mutate(&mut state, &mut input).unwrap();
+            assert_eq!(res, MutationResult::Mutated);
+            assert_eq!(input.tree().symbol, "<start>");
+            assert_eq!(input.tree().to_bytes().len(), 2);
+        }
+        assert!(mutator.fandango.symbols.iter().all(|s| s == "<digit>"));
+    }
+
+    #[test]
+    fn subtree_mutator_regenerates_a_lone_root() {
+        let mut mutator = FandangoSubtreeMutator::new(Regenerate::default());
+        let mut state: NopState<FandangoTreeInput> = NopState::new();
+        let mut input = FandangoTreeInput::new(node("<start>", vec![leaf(b'1')]));
+        let res = mutator.mutate(&mut state, &mut input).unwrap();
+        assert_eq!(res, MutationResult::Mutated);
+        assert_eq!(mutator.fandango.symbols, ["<start>"]);
+        assert_eq!(input.tree().to_bytes(), b"x");
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    ops::{Deref, DerefMut},
+};
+
+use libafl::{
+    Error,
+    executors::ExitKind,
+    inputs::HasTargetBytes,
+    observers::{MapObserver, Observer},
+};
+use libafl_bolts::{HasLen, Named, hash_std};
+use serde::{Deserialize, Serialize};
+
+use crate::fandango::{DerivationTree, FandangoClient};
+
+/// What a single entry of a [`FandangoGrammarCoverageObserver`] map stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GrammarCoverageGranularity {
+    /// One entry per nonterminal, e.g. `<digit>`.
+    Nonterminal,
+    /// One entry per expansion, i.e. a nonterminal together with the symbols of its children
+    /// (`<start> ::= <leading_digit> <digit>`). Repetitions produce one entry per observed length.
+    Expansion,
+}
+
+/// A map observer for grammar coverage.
+///
+/// After every execution, the input is parsed with the wrapped [`FandangoClient`] and each node of
+/// the first derivation tree bumps the hit count of its key (see [`GrammarCoverageGranularity`]).
+/// Keys are hashed into the map with a fixed hash, so indices are stable across processes. Use it
+/// with e.g. `MaxMapFeedback` to get a novelty signal from the spec next to code coverage.
+///
+/// Only the map and the keys seen so far are serialized; a deserialized copy has no client and
+/// does not parse anything.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct FandangoGrammarCoverageObserver<F> {
+    name: Cow<'static, str>,
+    map: Vec<u8>,
+    granularity: GrammarCoverageGranularity,
+    seen: BTreeMap<String, usize>,
+    #[serde(skip)]
+    fandango: Option<F>,
+}
+
+impl<F> FandangoGrammarCoverageObserver<F> {
+    pub fn new(
+        name: &'static str,
+        fandango: F,
+        map_size: usize,
+        granularity: GrammarCoverageGranularity,
+    ) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            map: vec![0; map_size.max(1)],
+            granularity,
+            seen: BTreeMap::new(),
+            fandango: Some(fandango),
+        }
+    }
+
+    /// Every key (nonterminal or expansion) observed so far, with its map index.
+    ///
+    /// Anything in the spec that never shows up here has not been exercised by the campaign.
+    pub fn seen(&self) -> &BTreeMap<String, usize> {
+        &self.seen
+    }
+
+    fn key(&self, node: &DerivationTree) -> String {
+        match self.granularity {
+            GrammarCoverageGranularity::Nonterminal => node.symbol.clone(),
+            GrammarCoverageGranularity::Expansion => {
+                let children: Vec<&str> = node.children.iter().map(|c| c.symbol.as_str()).collect();
+                format!("{} ::= {}", node.symbol, children.join(" "))
+            }
+        }
+    }
+
+    fn record(&mut self, tree: &DerivationTree) {
+        for node in tree.iter().filter(|n| n.is_nonterminal()) {
+            let key = self.key(node);
+            let index = match self.seen.get(&key) {
+                Some(&index) => index,
+                None => {
+                    let index = (hash_std(key.as_bytes()) % self.map.len() as u64) as usize;
+                    self.seen.insert(key, index);
+                    index
+                }
+            };
+            self.map[index] = self.map[index].saturating_add(1);
+        }
+    }
+}
+
+impl<F, I, S> Observer<I, S> for FandangoGrammarCoverageObserver<F>
+where
+    F: FandangoClient,
+    I: HasTargetBytes,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.reset_map()
+    }
+
+    fn post_exec(&mut self, _state: &mut S, input: &I, _exit_kind: &ExitKind) -> Result<(), Error> {
+        let Some(fandango) = self.fandango.as_mut() else {
+            return Ok(());
+        };
+        let trees = fandango.parse_trees(&input.target_bytes())?;
+        if let Some(tree) = trees.first() {
+            self.record(tree);
+        }
+        Ok(())
+    }
+}
+
+impl<F> Named for FandangoGrammarCoverageObserver<F> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<F> HasLen for FandangoGrammarCoverageObserver<F> {
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+impl<F> AsRef<Self> for FandangoGrammarCoverageObserver<F> {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
+impl<F> AsMut<Self> for FandangoGrammarCoverageObserver<F> {
+    fn as_mut(&mut self) -> &mut Self {
+        self
+    }
+}
+
+// Through `Deref` and `DerefMut`, libafl_bolts' blanket impls provide `AsSlice`/`AsIter` and
+// `AsSliceMut`/`AsIterMut`, which `MapFeedback` requires; a direct impl would conflict with them.
+impl<F> Deref for FandangoGrammarCoverageObserver<F> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+impl<F> DerefMut for FandangoGrammarCoverageObserver<F> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.map
+    }
+}
+
+impl<F> MapObserver for FandangoGrammarCoverageObserver<F> {
+    type Entry = u8;
+
+    fn get(&self, idx: usize) -> u8 {
+        self.map[idx]
+    }
+
+    fn set(&mut self, idx: usize, val: u8) {
+        self.map[idx] = val;
+    }
+
+    fn usable_count(&self) -> usize {
+        self.map.len()
+    }
+
+    fn count_bytes(&self) -> u64 {
+        self.map.iter().filter(|&&x| x != 0).count() as u64
+    }
+
+    fn initial(&self) -> u8 {
+        0
+    }
+
+    fn reset_map(&mut self) -> Result<(), Error> {
+        self.map.fill(0);
+        Ok(())
+    }
+
+    fn to_vec(&self) -> Vec<u8> {
+        self.map.clone()
+    }
+
+    fn how_many_set(&self, indexes: &[usize]) -> usize {
+        indexes
+            .iter()
+            .filter(|&&i| self.map.get(i).is_some_and(|&x| x != 0))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl::{
+        corpus::{Corpus, InMemoryCorpus},
+        events::NopEventManager,
+        executors::InProcessExecutor,
+        feedbacks::{CrashFeedback, MaxMapFeedback},
+        fuzzer::{Evaluator, StdFuzzer},
+        inputs::BytesInput,
+        schedulers::QueueScheduler,
+        state::{HasCorpus, StdState},
+    };
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list};
+
+    use super::*;
+    use crate::fandango::FandangoCallError;
+
+    /// Parses every input as `<start> ::= <digit>*` with one `<digit>` per byte.
+    struct Digits;
+
+    impl FandangoClient for Digits {
+        fn next_input(&mut self) -> Result<Vec<u8>, FandangoCallError> {
+            Err(FandangoCallError::Exhausted)
+        }
+
+        fn parse_input(&mut self, _input: &[u8]) -> Result<u32, FandangoCallError> {
+            Ok(1)
+        }
+
+        fn parse_trees(&mut self, input: &[u8]) -> Result<Vec<DerivationTree>, FandangoCallError> {
+            let digits = input
+                .iter()
+                .enumerate()
+                .map(|(i, &b)| DerivationTree {
+                    symbol: "<digit>".to_string(),
+                    terminal: None,
+                    children: vec![DerivationTree {
+                        symbol: format!("'{}'", b as char),
+                        terminal: Some(vec![b]),
+                        children: Vec::new(),
+                        span: i..i + 1,
+                    }],
+                    span: i..i + 1,
+                })
+                .collect();
+            Ok(vec![DerivationTree {
+                symbol: "<start>".to_string(),
+                terminal: None,
+                children: digits,
+                span: 0..input.len(),
+            }])
+        }
+    }
+
+    #[test]
+    fn max_map_feedback_keeps_inputs_with_new_expansions() {
+        let observer = FandangoGrammarCoverageObserver::new(
+            "grammar",
+            Digits,
+            1 << 16,
+            GrammarCoverageGranularity::Expansion,
+        );
+        let mut feedback = MaxMapFeedback::new(&observer);
+        let mut objective = CrashFeedback::new();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+        let mut mgr = NopEventManager::new();
+        let mut harness = |_: &BytesInput| ExitKind::Ok;
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(observer),
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+        )
+        .unwrap();
+
+        // `12` adds `<start> ::= <digit> <digit>` and `<digit> ::= '2'`; the repeated `1` nothing.
+        for (input, corpus_size) in [(&b"1"[..], 1), (b"1", 1), (b"12", 2)] {
+            fuzzer
+                .evaluate_input(
+                    &mut state,
+                    &mut executor,
+                    &mut mgr,
+                    &BytesInput::new(input.to_vec()),
+                )
+                .unwrap();
+            assert_eq!(state.corpus().count(), corpus_size);
+        }
+    }
+}
@@ -24,7 +24,7 @@ impl<F, M> FandangoPostMutationalStage<F, M> {
     ///
     /// # Arguments
     ///
-    /// * `fandango` - A [`FandangoClient`] (e.g. [`crate::fandango::FandangoPythonModule`] or [`crate::FandangoSubprocess`])
+    /// * `fandango` - A [`FandangoClient`] (e.g. [`crate::fandango::FandangoInprocessModule`] or [`crate::fandango::FandangoSubprocessModule`], optionally wrapped in a [`crate::fandango::FandangoPrefetchClient`])
     /// * `mutators` - The mutators to use
     /// * `min_iterations` - The minimum number of iterations to run for each generated input (inclusive)
     /// * `max_iterations` - The maximum number of iterations to run for each generated input (inclusive)
@@ -52,11 +52,7 @@ where
         state: &mut S,
         manager: &mut EM,
     ) -> Result<(), libafl::Error> {
-        let input: ValueInput<Vec<u8>> = self
-            .fandango
-            .next_input()
-            .map_err(|e| Error::illegal_state(format!("Fandango error: {e}")))?
-            .into();
+        let input: ValueInput<Vec<u8>> = self.fandango.next_input()?.into();
 
         let iterations = 1 + state
             .rand_mut()